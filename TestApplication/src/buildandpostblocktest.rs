@@ -1,39 +1,133 @@
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap},
+    fs::read_to_string,
+    path::Path,
     process::{Command, Stdio},
 };
 
-use iota_sdk::client::{api::ClientBlockBuilderOptions, Client};
+use anyhow::{anyhow, Context};
+use chrono::Utc;
+use iota_sdk::{
+    client::{node_manager::node::NodeAuth, Client},
+    pow::miner::MinerBuilder,
+    types::block::{
+        parent::Parents,
+        payload::{Payload, TaggedDataPayload},
+        BlockBuilder,
+    },
+};
 use log::{info, warn};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json::to_string_pretty;
 use strum::IntoEnumIterator;
 use tokio::time::{sleep, Duration, Instant};
 
 use crate::{
-    graph::{draw_action_measurements, get_and_create_folder},
+    graph::{draw_action_measurements, draw_stacked_phase_plot, get_and_create_folder},
+    network::IotaTangleNetwork,
+    nodepool::{merge_node_usage, print_node_usage, NodePool, NodeStrategy, NodeUsage},
+    statistics::{calculate_stats, LatencyHistogram, Stats},
     utils::{
-        calculate_stats, print_measurement_stats, save_to_raw_data_file, save_to_results_file,
-        utf8_to_hex, wait_until_enter_pressed, Action, IotaTangleNetwork, Measurement,
-        MeasurementResult, Stats,
+        durations, phase_durations, print_measurement_stats, print_phase_stats,
+        save_to_raw_data_file, save_to_results_file, wait_until_enter_pressed, Action, Measurement,
+        MeasurementResult, Phase, Sample,
     },
 };
 
-#[derive(Debug, Clone, Copy, Serialize)]
-struct BuildBlockAndPublishStatResult {
+/// Results of one node configuration, saved in the `results` file of a block test run.
+/// Older `results` files do not record the node configuration, [`load_block_test_results`]
+/// fills it in.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BuildBlockAndPublishStatResult {
+    /// Number of running nodes
+    #[serde(default)]
+    pub node_count: usize,
+    #[serde(default)]
+    pub local_pow: bool,
+    #[serde(default)]
+    pub min_pow_score: usize,
     pub blocks: usize,
     pub bps: f64,
     pub duartion: f64,
     pub failures: usize,
     pub stats: Stats,
+    /// Blocks posted per node URL
+    #[serde(default)]
+    pub node_usage: HashMap<String, NodeUsage>,
+    /// Statistics of the phases of building and posting a block
+    #[serde(default)]
+    pub phases: BTreeMap<Phase, Stats>,
+}
+
+/// PoW settings of the block test runs whose `results` file does not record them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PowSettings {
+    pub local_pow: Option<bool>,
+    pub min_pow_score: Option<usize>,
+}
+
+/// A result as stored in a `results` file, with the node configuration if the file records it.
+#[derive(Deserialize)]
+struct ResultRecord {
+    node_count: Option<usize>,
+    local_pow: Option<bool>,
+    min_pow_score: Option<usize>,
+    #[serde(flatten)]
+    result: BuildBlockAndPublishStatResult,
+}
+
+/// Loads the `results` file of a block test run folder. A missing node count is taken from the
+/// node configuration the result is saved under, missing PoW settings from `fallback`.
+pub fn load_block_test_results(
+    folder_path: &Path,
+    fallback: PowSettings,
+) -> anyhow::Result<HashMap<Action, BuildBlockAndPublishStatResult>> {
+    let file_name = folder_path.join("results");
+    let json_data = read_to_string(&file_name)
+        .with_context(|| format!("failed to read {}", file_name.display()))?;
+    let records: HashMap<Action, ResultRecord> = serde_json::from_str(&json_data)
+        .with_context(|| format!("invalid {}", file_name.display()))?;
+
+    records
+        .into_iter()
+        .map(|(action, record)| {
+            let missing = |setting: &str, option: &str| {
+                anyhow!(
+                    "{} does not record the {} of {}, pass {}",
+                    file_name.display(),
+                    setting,
+                    action.name(),
+                    option
+                )
+            };
+            let result = BuildBlockAndPublishStatResult {
+                node_count: record
+                    .node_count
+                    .or(action.node_count())
+                    .ok_or_else(|| anyhow!("unknown node count of {}", action.name()))?,
+                local_pow: record
+                    .local_pow
+                    .or(fallback.local_pow)
+                    .ok_or_else(|| missing("PoW mode", "--local-pow <true|false>"))?,
+                min_pow_score: record
+                    .min_pow_score
+                    .or(fallback.min_pow_score)
+                    .ok_or_else(|| missing("min PoW score", "--min-pow-score"))?,
+                ..record.result
+            };
+            Ok((action, result))
+        })
+        .collect()
 }
 
 pub async fn run_for_all_nodes_configurations_block_test(
+    networks: &[IotaTangleNetwork],
+    node_strategy: NodeStrategy,
     number_of_tasks: usize,
     number_of_iterations: usize,
     local_pow: bool,
     min_pow_score: usize,
-) {
+) -> anyhow::Result<()> {
     let configurations = vec![Action::nodes_4, Action::nodes_3, Action::nodes_2];
     let mut measurement = Measurement::new();
     let mut result_stats: HashMap<Action, BuildBlockAndPublishStatResult> = HashMap::new();
@@ -42,13 +136,16 @@ pub async fn run_for_all_nodes_configurations_block_test(
     for (index, action) in configurations.iter().enumerate() {
         build_and_post_block_test(
             &action,
+            networks,
+            node_strategy,
             &mut measurement,
             &mut result_stats,
             number_of_tasks,
             number_of_iterations,
             local_pow,
+            min_pow_score,
         )
-        .await;
+        .await?;
 
         // Turn off one node
         if index != configurations.len() - 1 {
@@ -56,22 +153,7 @@ pub async fn run_for_all_nodes_configurations_block_test(
             start_node_number -= 1;
             info!("Turn off one node {}", image_name);
 
-            let result = Command::new("docker")
-                .arg("compose")
-                .arg("stop")
-                .arg(image_name)
-                .current_dir("../PrivateTangle")
-                .status();
-
-            match result {
-                Ok(output) => {
-                    // let stdout = String::from_utf8_lossy(&output.stdout);
-                    info!("Command Output:\n{}", output);
-                }
-                Err(e) => {
-                    warn!("Failed to execute command: {}", e);
-                }
-            }
+            docker_compose("stop", &[&image_name]);
             sleep(Duration::from_secs(10)).await;
         }
     }
@@ -90,13 +172,13 @@ pub async fn run_for_all_nodes_configurations_block_test(
     println!("Min PoW Score {}", min_pow_score);
 
     println!(
-        "{0: <10} | {1: <10} | {2: <10} | {3: <10} | {4: <10} | {5: <10} | {6: <10} | {7: <10} | {8: <10}",
-        "Action", "Blocks", "Error", "Duration", "BPS", "Min", "Max", "Mean", "Variance"
+        "{0: <10} | {1: <10} | {2: <10} | {3: <10} | {4: <10} | {5: <8} | {6: <8} | {7: <8} | {8: <8} | {9: <8} | {10: <8} | {11: <8} | {12: <8} | {13: <8}",
+        "Action", "Blocks", "Error", "Duration", "BPS", "Min", "Max", "Mean", "Std dev", "p50", "p90", "p95", "p99", "p99.9"
     );
     for action in Action::iter() {
         if let Some(stats) = result_stats.get(&action) {
             println!(
-            "{0: <10} | {1: <10} | {2: <10} | {3: <10.3} | {4: <10.3} | {5: <10.4} | {6: <10.4} | {7: <10.4} | {8: <10.4e}",
+            "{0: <10} | {1: <10} | {2: <10} | {3: <10.3} | {4: <10.3} | {5: <8.4} | {6: <8.4} | {7: <8.4} | {8: <8.4} | {9: <8.4} | {10: <8.4} | {11: <8.4} | {12: <8.4} | {13: <8.4}",
             action.name(),
             stats.blocks,
             stats.failures,
@@ -105,11 +187,25 @@ pub async fn run_for_all_nodes_configurations_block_test(
             stats.stats.min,
             stats.stats.max,
             stats.stats.mean,
-            stats.stats.variance,
+            stats.stats.std_dev,
+            stats.stats.p50,
+            stats.stats.p90,
+            stats.stats.p95,
+            stats.stats.p99,
+            stats.stats.p999,
         );
         }
     }
 
+    print_phase_stats(&measurement);
+
+    for action in Action::iter() {
+        if let Some(stats) = result_stats.get(&action) {
+            println!("Node usage {}", action.name());
+            print_node_usage(&stats.node_usage);
+        }
+    }
+
     let local_pow_string = if local_pow { "Local PoW" } else { "Remote PoW" };
     let plot_title = format!(
         "{} (MinPoWScore: {}, {})",
@@ -118,73 +214,147 @@ pub async fn run_for_all_nodes_configurations_block_test(
         local_pow_string
     );
     draw_action_measurements(&plot_title, &measurement, &folder_name);
+    draw_stacked_phase_plot(&folder_name, &plot_title, &result_stats);
+    Ok(())
+}
+
+/// Runs the block test with remote PoW and then with local PoW.
+/// The nodes turned off by the first run are started again before the second run.
+pub async fn run_for_both_pow_modes_block_test(
+    networks: &[IotaTangleNetwork],
+    node_strategy: NodeStrategy,
+    number_of_tasks: usize,
+    number_of_iterations: usize,
+    min_pow_score: usize,
+) -> anyhow::Result<()> {
+    for local_pow in [false, true] {
+        run_for_all_nodes_configurations_block_test(
+            networks,
+            node_strategy,
+            number_of_tasks,
+            number_of_iterations,
+            local_pow,
+            min_pow_score,
+        )
+        .await?;
+
+        docker_compose("start", &["hornet-3", "hornet-4"]);
+        sleep(Duration::from_secs(60)).await;
+    }
+    Ok(())
+}
+
+fn docker_compose(command: &str, services: &[&str]) {
+    let result = Command::new("docker")
+        .arg("compose")
+        .arg(command)
+        .args(services)
+        .current_dir("../PrivateTangle")
+        .status();
+
+    match result {
+        Ok(output) => {
+            info!("Command Output:\n{}", output);
+        }
+        Err(e) => {
+            warn!("Failed to execute command: {}", e);
+        }
+    }
 }
 
 async fn build_and_post_block_test(
     action: &Action,
+    networks: &[IotaTangleNetwork],
+    node_strategy: NodeStrategy,
     measurements: &mut Measurement,
     results: &mut HashMap<Action, BuildBlockAndPublishStatResult>,
     number_of_tasks: usize,
     number_of_iterations: usize,
     local_pow: bool,
-) {
+    min_pow_score: usize,
+) -> anyhow::Result<()> {
     let mut tasks = Vec::new();
     let mut result = MeasurementResult::new();
+    let mut node_usage = HashMap::new();
+    let mut histogram = LatencyHistogram::new();
     let test_start = Instant::now();
     info!("--------------------------------------------------");
 
+    // Every node of the given networks, with the authentication of its network
+    let nodes: Vec<(String, Option<NodeAuth>)> = networks
+        .iter()
+        .flat_map(|network| {
+            network
+                .api_endpoints
+                .iter()
+                .map(|url| (url.clone(), network.node_auth()))
+        })
+        .collect();
+    if nodes.is_empty() {
+        anyhow::bail!("the networks of the block test have no API endpoints");
+    }
+
     for index in 0..number_of_tasks {
         let action = action.clone();
         let number_of_iterations = number_of_iterations.clone();
         let local_pow = local_pow.clone();
-        let network = if index % 2 == 0 {
-            IotaTangleNetwork::Localhost
-        } else {
-            IotaTangleNetwork::Localhost2
-        };
+        // Start every task at a different node, so the primary nodes are spread over the nodes
+        let mut task_nodes = nodes.clone();
+        task_nodes.rotate_left(index % nodes.len());
 
+        let task_id = index;
         tasks.push(tokio::spawn(async move {
             let mut result = MeasurementResult::new();
-
-            let client_builder = Client::builder()
-                .with_local_pow(local_pow)
-                .with_fallback_to_local_pow(local_pow)
-                .with_primary_node(&network.api_endpoint(), None);
-
-            match client_builder {
-                Ok(builder) => match builder.finish().await {
-                    Ok(client) => {
-                        // let local_pow = client.get_local_pow().await;
-                        // info!("Local PoW {}", local_pow);
-                        // let info = client.get_info().await.unwrap().node_info;
-                        // info!("{info}");
-
-                        let action_measurements =
-                            result.measurement.entry(action).or_insert_with(Vec::new);
-
-                        for _ in 0..number_of_iterations {
-                            let start = Instant::now();
-                            match build_and_post_block(&client, network).await {
-                                Ok(_) => {
-                                    let duration = start.elapsed();
-                                    action_measurements.push(duration.as_secs_f64());
-                                }
-                                Err(e) => {
-                                    result.failures += 1;
-                                    warn!("Failed to post block: {:?}", e);
-                                }
-                            };
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Failed to create client: {:?}", e);
+            let mut node_usage = HashMap::new();
+            let mut histogram = LatencyHistogram::new();
+
+            let pool = NodePool::new(task_nodes, node_strategy, |builder| {
+                builder
+                    .with_local_pow(local_pow)
+                    .with_fallback_to_local_pow(local_pow)
+            })
+            .await;
+
+            match pool {
+                Ok(pool) => {
+                    // let local_pow = client.get_local_pow().await;
+                    // info!("Local PoW {}", local_pow);
+                    // let info = client.get_info().await.unwrap().node_info;
+                    // info!("{info}");
+
+                    let action_measurements =
+                        result.measurement.entry(action).or_insert_with(Vec::new);
+
+                    for iteration in 0..number_of_iterations {
+                        let node = pool.select();
+                        let start_time = Utc::now();
+                        let start = Instant::now();
+                        match build_and_post_block(pool.client(node), pool.url(node)).await {
+                            Ok(phases) => {
+                                let duration = start.elapsed();
+                                pool.record(node, duration, true);
+                                histogram.record(duration.as_secs_f64());
+                                action_measurements.push(
+                                    Sample::new(start_time, duration, task_id, iteration)
+                                        .with_node(pool.url(node))
+                                        .with_phases(phases),
+                                );
+                            }
+                            Err(e) => {
+                                pool.record(node, start.elapsed(), false);
+                                result.failures += 1;
+                                warn!("Failed to post block to {}: {:?}", pool.url(node), e);
+                            }
+                        };
                     }
-                },
+
+                    node_usage = pool.usage();
+                }
                 Err(e) => {
-                    warn!("Failed to build client: {:?}", e);
+                    warn!("Failed to create node pool: {:?}", e);
                 }
             }
-            result
+            (result, node_usage, histogram)
         }));
     }
 
@@ -193,12 +363,14 @@ async fn build_and_post_block_test(
 
     for handle in tasks {
         match handle.await {
-            Ok(mut task_result) => {
+            Ok((mut task_result, task_node_usage, task_histogram)) => {
                 for (action, durations) in &mut task_result.measurement {
                     let element = result.measurement.entry(*action).or_insert_with(Vec::new);
                     element.append(durations);
                 }
                 result.failures += task_result.failures;
+                merge_node_usage(&mut node_usage, &task_node_usage);
+                histogram.merge(&task_histogram);
             }
             Err(err) => {
                 warn!("Invalid thread results: {:?}", err);
@@ -218,60 +390,149 @@ async fn build_and_post_block_test(
     // print_measurement_stats(&result.measurement);
 
     let mut result_stats = BuildBlockAndPublishStatResult {
+        node_count: action.node_count().unwrap_or(nodes.len()),
+        local_pow,
+        min_pow_score,
         blocks: number_of_blocks,
         bps: blocks_per_second,
         duartion: test_duration.as_secs_f64(),
         failures: result.failures,
         stats: Stats::default(),
+        node_usage,
+        phases: BTreeMap::new(),
     };
 
-    for (_action, durations) in &mut result.measurement {
-        result_stats.stats = calculate_stats(durations);
+    for (_action, samples) in &mut result.measurement {
+        // Exact values from the samples, percentiles from the merged task histograms
+        result_stats.stats = Stats::new(&durations(samples), &histogram);
+        for phase in Phase::iter() {
+            let durations = phase_durations(samples, phase);
+            if !durations.is_empty() {
+                result_stats
+                    .phases
+                    .insert(phase, calculate_stats(&durations));
+            }
+        }
 
         // Copy measurements
         let element = measurements.entry(*action).or_insert_with(Vec::new);
-        element.append(durations);
+        element.append(samples);
     }
 
     results.insert(*action, result_stats);
     info!("--------------------------------------------------");
+    Ok(())
 }
 
-async fn build_and_post_block(client: &Client, network: IotaTangleNetwork) -> anyhow::Result<()> {
-    // info!("build_and_post_block");
-
-    let dsa = format!("Hello tag {:?}", network.api_endpoint());
-
-    let tag = utf8_to_hex(&dsa);
-    let data = utf8_to_hex("Hello data");
-
-    // ClientBlockBuilderOptions
-    let options = ClientBlockBuilderOptions {
-        coin_type: None,
-        account_index: None,
-        initial_address_index: None,
-        inputs: None,
-        input_range: None,
-        output: None,
-        output_hex: None,
-        outputs: None,
-        custom_remainder_address: None,
-        tag: Some(tag),
-        data: Some(data),
-        parents: None,
-        burn: None,
+/// Builds and posts a tagged data block, step by step like `build_block().finish()`, and returns
+/// the duration in seconds of each phase.
+async fn build_and_post_block(
+    client: &Client,
+    node_url: &str,
+) -> anyhow::Result<BTreeMap<Phase, f64>> {
+    let mut phases = BTreeMap::new();
+
+    let tag = format!("Hello tag {:?}", node_url);
+    let payload = Payload::from(TaggedDataPayload::new(
+        tag.into_bytes(),
+        b"Hello data".to_vec(),
+    )?);
+
+    let start = Instant::now();
+    let parents = Parents::from_vec(client.get_tips().await?)?;
+    phases.insert(Phase::TipSelection, start.elapsed().as_secs_f64());
+
+    let builder = BlockBuilder::new(parents).with_payload(payload);
+    let block = if client.get_local_pow().await {
+        let start = Instant::now();
+        let min_pow_score = client.get_min_pow_score().await?;
+        let miner = MinerBuilder::new()
+            .with_num_workers(num_cpus::get())
+            .finish();
+        let block = builder.finish_nonce(|bytes| miner.nonce(bytes, min_pow_score))?;
+        phases.insert(Phase::Pow, start.elapsed().as_secs_f64());
+        block
+    } else {
+        // Without a nonce, the node does the PoW when the block is posted
+        builder.finish()?
     };
 
-    let mut block_builder = client.build_block();
-    block_builder = block_builder.set_options(options).await?;
-    let _block = block_builder.finish().await?;
+    let start = Instant::now();
+    let _block_id = client.post_block(&block).await?;
+    phases.insert(Phase::Post, start.elapsed().as_secs_f64());
 
-    // info!("BlockId: {:?}", _block);
+    Ok(phases)
+}
 
-    // if network == IotaTangleNetwork::Localhost2 {
-    //     let block_id = _block.id();
-    //     info!("BlockId: {}", block_id);
-    // }
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn result(node_count: usize) -> BuildBlockAndPublishStatResult {
+        BuildBlockAndPublishStatResult {
+            node_count,
+            local_pow: true,
+            min_pow_score: 1500,
+            blocks: 100,
+            bps: 25.0,
+            duartion: 4.0,
+            failures: 0,
+            stats: Stats::default(),
+            node_usage: HashMap::new(),
+            phases: BTreeMap::new(),
+        }
+    }
 
-    Ok(())
+    fn write_results(folder_name: &str, json_data: &serde_json::Value) -> std::path::PathBuf {
+        let folder = std::env::temp_dir().join(format!("{}_{}", folder_name, std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        fs::write(folder.join("results"), json_data.to_string()).unwrap();
+        folder
+    }
+
+    #[test]
+    fn load_results_without_node_configuration() {
+        let mut json_data =
+            serde_json::to_value(HashMap::from([(Action::nodes_3, result(3))])).unwrap();
+        for field in ["node_count", "local_pow", "min_pow_score"] {
+            json_data["nodes_3"].as_object_mut().unwrap().remove(field);
+        }
+        let folder = write_results("old_block_test_results", &json_data);
+
+        assert!(load_block_test_results(&folder, PowSettings::default()).is_err());
+        let fallback = PowSettings {
+            local_pow: Some(false),
+            min_pow_score: Some(750),
+        };
+        let results = load_block_test_results(&folder, fallback).unwrap();
+        let result = &results[&Action::nodes_3];
+        assert_eq!(result.node_count, 3);
+        assert!(!result.local_pow);
+        assert_eq!(result.min_pow_score, 750);
+        assert_eq!(result.bps, 25.0);
+
+        fs::remove_dir_all(folder).unwrap();
+    }
+
+    #[test]
+    fn load_results_with_node_configuration() {
+        let json_data =
+            serde_json::to_value(HashMap::from([(Action::nodes_2, result(2))])).unwrap();
+        let folder = write_results("block_test_results", &json_data);
+
+        // The recorded configuration wins over the fallback
+        let fallback = PowSettings {
+            local_pow: Some(false),
+            min_pow_score: Some(0),
+        };
+        let results = load_block_test_results(&folder, fallback).unwrap();
+        let result = &results[&Action::nodes_2];
+        assert_eq!(result.node_count, 2);
+        assert!(result.local_pow);
+        assert_eq!(result.min_pow_score, 1500);
+
+        fs::remove_dir_all(folder).unwrap();
+    }
 }
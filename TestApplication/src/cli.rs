@@ -0,0 +1,332 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+use crate::{
+    consistency::ConsistencyConfig,
+    documentsize::DocumentGrowth,
+    failure::FailureKind,
+    keystorage::{KeyAlgorithm, KeyStorageConfig, StorageBackend},
+    nodepool::NodeStrategy,
+    retry::RetryPolicy,
+    utils::Action,
+    Params,
+};
+
+#[derive(Debug, Parser)]
+#[command(about = "Performance tests for IOTA Identity on a Tangle network")]
+pub struct Cli {
+    /// TOML file with additional networks. Defaults to ./networks.toml if it exists.
+    #[arg(long, global = true)]
+    pub networks_file: Option<PathBuf>,
+
+    /// Start a mock node for the `mock` network in this process before running the command
+    #[arg(long, global = true)]
+    pub with_mock_node: bool,
+
+    /// TOML fault profile of the mock node started with --with-mock-node
+    #[arg(long, global = true, requires = "with_mock_node")]
+    pub mock_faults: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create and update DIDs on one or more networks
+    DidBench {
+        /// Test parameters as <num_threads>x<iterations>, e.g. 5x100. Can be repeated.
+        #[arg(short, long = "params", value_parser = parse_params, default_value = "5x10")]
+        params: Vec<Params>,
+
+        /// Ids of the networks to run the test against. Can be repeated.
+        #[arg(short, long = "network", default_value = "localhost")]
+        networks: Vec<String>,
+
+        /// Actions run by every task, in order. Can be repeated.
+        #[arg(short, long = "action", value_enum, default_values = ["create-did", "update-did"])]
+        actions: Vec<Action>,
+
+        /// Folder where the results are saved
+        #[arg(short, long, default_value = "test")]
+        output_dir: String,
+
+        /// How each task spreads its requests over the API endpoints of a network
+        #[arg(long, value_enum, default_value_t = NodeStrategy::default())]
+        node_strategy: NodeStrategy,
+
+        #[command(flatten)]
+        retry: RetryArgs,
+
+        /// Time every action until its block is included by a milestone and the new state resolves
+        #[arg(long)]
+        confirmation: bool,
+
+        /// Query the wallet balance before and after every action that changes a DID
+        #[arg(long)]
+        track_cost: bool,
+
+        #[command(flatten)]
+        consistency: ConsistencyArgs,
+
+        /// Folder where every task saves its DIDs and stronghold, and loads them from on the next run
+        #[arg(long)]
+        state_dir: Option<PathBuf>,
+
+        #[command(flatten)]
+        key_storage: KeyStorageArgs,
+    },
+
+    /// Run a DID benchmark described by a TOML or YAML scenario file
+    Run { scenario: PathBuf },
+
+    /// Create one DID and resolve it concurrently
+    ResolveBench {
+        #[arg(short, long, default_value = "localhost")]
+        network: String,
+
+        #[arg(short = 't', long, default_value_t = 50)]
+        num_threads: usize,
+
+        #[arg(short, long, default_value_t = 100)]
+        iterations: usize,
+
+        /// How each task spreads its requests over the API endpoints of the network
+        #[arg(long, value_enum, default_value_t = NodeStrategy::default())]
+        node_strategy: NodeStrategy,
+
+        #[command(flatten)]
+        key_storage: KeyStorageArgs,
+    },
+
+    /// Create and update DIDs with growing documents and plot the latency against the document size
+    SizeBench {
+        #[arg(short, long, default_value = "localhost")]
+        network: String,
+
+        /// What is added to the documents. Can be repeated.
+        #[arg(short, long = "growth", value_enum, default_values = ["methods", "services", "properties"])]
+        growths: Vec<DocumentGrowth>,
+
+        /// Numbers of elements added to the documents, e.g. 0,5,10
+        #[arg(short, long = "count", value_delimiter = ',', default_values_t = [0, 5, 10, 20])]
+        counts: Vec<usize>,
+
+        /// DIDs created and updated per size
+        #[arg(short, long, default_value_t = 10)]
+        iterations: usize,
+
+        /// Folder where the results are saved
+        #[arg(short, long, default_value = "test")]
+        output_dir: String,
+
+        /// How the requests are spread over the API endpoints of the network
+        #[arg(long, value_enum, default_value_t = NodeStrategy::default())]
+        node_strategy: NodeStrategy,
+
+        #[command(flatten)]
+        key_storage: KeyStorageArgs,
+    },
+
+    /// Build and post blocks while turning off one node at a time
+    BlockBench {
+        /// Ids of the networks whose nodes the tasks post to. Can be repeated.
+        #[arg(short, long = "network", default_values = ["localhost", "localhost2"])]
+        networks: Vec<String>,
+
+        /// How each task spreads its blocks over the nodes. Every task starts at a different node.
+        #[arg(long, value_enum, default_value_t = NodeStrategy::default())]
+        node_strategy: NodeStrategy,
+
+        #[arg(short = 't', long, default_value_t = 2)]
+        num_tasks: usize,
+
+        #[arg(short, long, default_value_t = 10_000)]
+        iterations: usize,
+
+        /// Do the proof of work on the client instead of the node
+        #[arg(long)]
+        local_pow: bool,
+
+        /// Min PoW score configured on the nodes. Only used for the title of the graph.
+        #[arg(long, default_value_t = 0)]
+        min_pow_score: usize,
+
+        /// Run with remote PoW first and then with local PoW, restarting the stopped nodes in between
+        #[arg(long, conflicts_with = "local_pow")]
+        both_pow_modes: bool,
+    },
+
+    /// Generate the BPS line graphs from the results of block test runs
+    Plot {
+        #[arg(value_enum)]
+        kind: PlotKind,
+
+        /// Result folders written by block-bench
+        #[arg(required = true)]
+        folders: Vec<PathBuf>,
+
+        /// PoW mode of the runs whose results do not record it
+        #[arg(long)]
+        local_pow: Option<bool>,
+
+        /// Min PoW score of the runs whose results do not record it
+        #[arg(long)]
+        min_pow_score: Option<usize>,
+    },
+
+    /// Test whether the latencies of two raw_data files differ significantly
+    Compare {
+        baseline: String,
+        candidate: String,
+
+        /// Significance level
+        #[arg(long, default_value_t = 0.05)]
+        alpha: f64,
+    },
+
+    /// Run a mock node with a faucet for the `mock` network, to test without Docker or a testnet
+    MockNode {
+        #[arg(long, default_value_t = 14365)]
+        port: u16,
+
+        #[arg(long, default_value_t = 0)]
+        min_pow_score: u32,
+
+        /// Milliseconds between two milestones
+        #[arg(long, default_value_t = 1000)]
+        milestone_interval: u64,
+
+        /// TOML file with the latency, errors and other faults to inject
+        #[arg(long)]
+        faults: Option<PathBuf>,
+    },
+
+    /// Print the statistics of raw_data files or results folders.
+    /// With two or more, also compare them side by side and draw grouped box plots.
+    Report {
+        #[arg(required = true)]
+        files: Vec<String>,
+
+        /// Folder where the comparison plots are saved
+        #[arg(short, long, default_value = "test/reports")]
+        output_dir: String,
+    },
+}
+
+/// Retry policy of the DID operations.
+#[derive(Debug, Args)]
+pub struct RetryArgs {
+    /// Attempts per DID operation, including the first one. 1 disables retries.
+    #[arg(long, default_value_t = 1)]
+    pub max_attempts: u32,
+
+    /// Backoff before the first retry in milliseconds, doubled on every further retry
+    #[arg(long, default_value_t = 500)]
+    pub initial_backoff_ms: u64,
+
+    #[arg(long, default_value_t = 10_000)]
+    pub max_backoff_ms: u64,
+
+    /// Share of the backoff that is randomized, between 0 and 1
+    #[arg(long, default_value_t = 0.5)]
+    pub jitter: f64,
+
+    /// Failure kinds that are retried. Can be repeated.
+    #[arg(long, value_enum, default_values = ["conflicting-output", "node-server-error", "node-unreachable"])]
+    pub retry_on: Vec<FailureKind>,
+}
+
+impl RetryArgs {
+    pub fn policy(&self) -> anyhow::Result<RetryPolicy> {
+        let policy = RetryPolicy {
+            max_attempts: self.max_attempts,
+            initial_backoff: Duration::from_millis(self.initial_backoff_ms),
+            max_backoff: Duration::from_millis(self.max_backoff_ms),
+            jitter: self.jitter,
+            retry_on: self.retry_on.clone(),
+            ..RetryPolicy::default()
+        };
+        policy.validate()?;
+        Ok(policy)
+    }
+}
+
+/// Read-after-write check of the DID operations.
+#[derive(Debug, Args)]
+pub struct ConsistencyArgs {
+    /// After every change, resolve the DID until the change is visible and record the delay
+    #[arg(long)]
+    pub check_consistency: bool,
+
+    /// Resolve from another node than the one the change was written to, needs at least two API endpoints
+    #[arg(long, requires = "check_consistency")]
+    pub cross_node: bool,
+
+    /// Milliseconds after which a change that is not visible counts as inconsistent
+    #[arg(long, default_value_t = 60_000)]
+    pub consistency_timeout_ms: u64,
+}
+
+impl ConsistencyArgs {
+    pub fn config(&self) -> Option<ConsistencyConfig> {
+        self.check_consistency.then(|| ConsistencyConfig {
+            cross_node: self.cross_node,
+            timeout: Duration::from_millis(self.consistency_timeout_ms),
+            ..ConsistencyConfig::default()
+        })
+    }
+}
+
+/// Where the keys of the DID benchmarks are kept.
+#[derive(Debug, Args)]
+pub struct KeyStorageArgs {
+    /// Key storage. Can be repeated to compare backends.
+    /// The stronghold password is read from STRONGHOLD_PASSWORD or asked for.
+    #[arg(long = "storage", value_enum, default_values = ["stronghold"])]
+    pub storages: Vec<StorageBackend>,
+
+    /// Key type and algorithm of the verification methods. Can be repeated to compare algorithms
+    #[arg(long = "key-algorithm", value_enum, default_values = ["ed25519"])]
+    pub key_algorithms: Vec<KeyAlgorithm>,
+
+    /// Keep the stronghold snapshots of the tasks in test_strongholds after the run
+    #[arg(long)]
+    pub keep_strongholds: bool,
+}
+
+impl KeyStorageArgs {
+    pub fn config(&self) -> KeyStorageConfig {
+        KeyStorageConfig {
+            backends: self.storages.clone(),
+            algorithms: self.key_algorithms.clone(),
+            password: None,
+            keep_strongholds: self.keep_strongholds,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum PlotKind {
+    NodeCount,
+    PowScore,
+}
+
+fn parse_params(value: &str) -> Result<Params, String> {
+    let (num_threads, iterations) = value
+        .split_once('x')
+        .ok_or_else(|| format!("expected <num_threads>x<iterations>, got `{}`", value))?;
+
+    Ok(Params {
+        num_threads: num_threads
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid number of threads `{}`: {}", num_threads, e))?,
+        iterations: iterations
+            .trim()
+            .parse()
+            .map_err(|e| format!("invalid number of iterations `{}`: {}", iterations, e))?,
+    })
+}
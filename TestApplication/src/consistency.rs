@@ -0,0 +1,166 @@
+use identity_iota::{
+    credential::RevocationBitmap,
+    did::DIDUrl,
+    document::Service,
+    iota::{IotaDID, IotaDocument},
+    prelude::Resolver,
+};
+use iota_sdk::client::node_api::error::Error as NodeError;
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep, Duration, Instant};
+
+/// How the state of a DID is verified after it was changed.
+#[derive(Debug, Clone)]
+pub struct ConsistencyConfig {
+    /// Resolve from another node of the pool than the one the change was written to.
+    pub cross_node: bool,
+    /// Time after which a missing state counts as an inconsistency.
+    pub timeout: Duration,
+    pub poll_interval: Duration,
+}
+
+impl Default for ConsistencyConfig {
+    fn default() -> Self {
+        Self {
+            cross_node: false,
+            timeout: Duration::from_secs(60),
+            poll_interval: Duration::from_millis(100),
+        }
+    }
+}
+
+/// State a DID resolves to once a change is visible.
+#[derive(Debug, Clone)]
+pub enum ExpectedState {
+    /// The document contains the verification method with this fragment.
+    HasMethod(String),
+    /// The document contains this service, with the same type and endpoint.
+    HasService(Service),
+    /// The document no longer contains the service with this id.
+    NoService(DIDUrl),
+    /// The document contains a revocation bitmap service with this fragment.
+    HasRevocationBitmap(String),
+    /// The revocation bitmap with this fragment has the credential index revoked or not.
+    Revocation {
+        fragment: String,
+        credential_index: u32,
+        revoked: bool,
+    },
+    Deactivated,
+    /// The document is no longer deactivated.
+    Active,
+    /// The Alias Output is gone, so the node answers with 404.
+    Deleted,
+}
+
+impl ExpectedState {
+    pub fn is_met(&self, resolved: &anyhow::Result<IotaDocument>) -> bool {
+        match (self, resolved) {
+            (ExpectedState::HasMethod(fragment), Ok(document)) => {
+                document.resolve_method(fragment.as_str(), None).is_some()
+            }
+            (ExpectedState::HasService(service), Ok(document)) => {
+                document.resolve_service(service.id()) == Some(service)
+            }
+            (ExpectedState::NoService(id), Ok(document)) => document.resolve_service(id).is_none(),
+            (ExpectedState::HasRevocationBitmap(fragment), Ok(document)) => document
+                .resolve_service(fragment.as_str())
+                .is_some_and(|service| RevocationBitmap::try_from(service).is_ok()),
+            (
+                ExpectedState::Revocation {
+                    fragment,
+                    credential_index,
+                    revoked,
+                },
+                Ok(document),
+            ) => document
+                .resolve_service(fragment.as_str())
+                .and_then(|service| RevocationBitmap::try_from(service).ok())
+                .is_some_and(|bitmap| bitmap.is_revoked(*credential_index) == *revoked),
+            (ExpectedState::Deactivated, Ok(document)) => {
+                document.metadata.deactivated == Some(true)
+            }
+            (ExpectedState::Active, Ok(document)) => document.metadata.deactivated != Some(true),
+            (ExpectedState::Deleted, Err(e)) => is_not_found(e),
+            _ => false,
+        }
+    }
+}
+
+/// The node answered with 404, any other error may hide a DID that still exists.
+fn is_not_found(error: &anyhow::Error) -> bool {
+    let not_found = |error: &NodeError| {
+        matches!(
+            error,
+            NodeError::NotFound(_) | NodeError::ResponseError { code: 404, .. }
+        )
+    };
+    error.chain().any(|cause| {
+        if let Some(iota_sdk::client::Error::Node(error)) = cause.downcast_ref() {
+            not_found(error)
+        } else if let Some(error) = cause.downcast_ref::<NodeError>() {
+            not_found(error)
+        } else {
+            false
+        }
+    })
+}
+
+/// Result of verifying the state of a DID after a change.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Consistency {
+    /// Node the DID was resolved from.
+    pub node: String,
+    /// Seconds from the end of the action until the expected state resolved, or until the check gave up.
+    pub delay: f64,
+    /// The expected state did not appear within the timeout.
+    pub inconsistent: bool,
+}
+
+/// Resolves `did` until it shows the `expected` state or the timeout of `config` is reached.
+pub async fn verify_state(
+    resolver: &Resolver<IotaDocument>,
+    node: &str,
+    did: &IotaDID,
+    expected: &ExpectedState,
+    config: &ConsistencyConfig,
+) -> Consistency {
+    let start = Instant::now();
+    loop {
+        let resolved = resolver.resolve(did).await.map_err(anyhow::Error::from);
+        let met = expected.is_met(&resolved);
+        if met || start.elapsed() >= config.timeout {
+            return Consistency {
+                node: node.to_string(),
+                delay: start.elapsed().as_secs_f64(),
+                inconsistent: !met,
+            };
+        }
+        sleep(config.poll_interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response_error(code: u16) -> anyhow::Result<IotaDocument> {
+        Err(iota_sdk::client::Error::Node(NodeError::ResponseError {
+            code,
+            text: "error".to_string(),
+            url: "http://localhost:14265/api/indexer/v1/outputs/alias".to_string(),
+        })
+        .into())
+    }
+
+    #[test]
+    fn deleted_only_on_not_found() {
+        assert!(ExpectedState::Deleted.is_met(&response_error(404)));
+        assert!(
+            ExpectedState::Deleted.is_met(&Err(NodeError::NotFound("alias".to_string()).into()))
+        );
+        assert!(!ExpectedState::Deleted.is_met(&response_error(400)));
+        assert!(!ExpectedState::Deleted.is_met(&response_error(429)));
+        assert!(!ExpectedState::Deleted.is_met(&response_error(503)));
+    }
+}
@@ -1,155 +1,622 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 
-use crate::utils::{get_address_with_funds, random_stronghold_path, Action};
-use anyhow::{anyhow, Ok};
+use crate::consistency::{verify_state, Consistency, ConsistencyConfig, ExpectedState};
+use crate::documentsize::{random_property, DocumentGrowth, PROPERTY_SIZE};
+use crate::failure::{
+    ConfirmationTimeout, ConflictingBlock, DocumentTooLarge, FailureKind, InvalidCredential,
+    MissingCredential, MissingDid, MissingService, ResolutionMismatch,
+};
+use crate::keystorage::{KeyAlgorithm, KeyStorage, KeyStorageConfig, StorageBackend};
+use crate::network::IotaTangleNetwork;
+use crate::nodepool::{NodePool, NodeStrategy, NodeUsage};
+use crate::retry::{Retries, RetryPolicy};
+use crate::utils::{
+    get_address_balance, get_address_with_funds, random_stronghold_path, Action, OutputSize, Phase,
+    TokenCost,
+};
+use anyhow::{anyhow, bail, Context};
+use identity_eddsa_verifier::EdDSAJwsVerifier;
 use identity_iota::{
-    core::Timestamp,
+    core::{FromJson, Object, Timestamp, Url},
+    credential::{
+        Credential, CredentialBuilder, DecodedJwtPresentation, FailFast, Jwt,
+        JwtCredentialValidationOptions, JwtCredentialValidator, JwtCredentialValidatorUtils,
+        JwtPresentationValidationOptions, JwtPresentationValidator, JwtPresentationValidatorUtils,
+        JwtValidationError, Presentation, PresentationBuilder, RevocationBitmap,
+        RevocationBitmapStatus, StatusCheck, Subject, SubjectHolderRelationship,
+    },
     did::{DIDUrl, DID},
-    iota::{IotaClientExt, IotaDID, IotaDocument, IotaIdentityClientExt, NetworkName},
+    document::{verifiable::JwsVerificationOptions, Service},
+    iota::{
+        IotaClientExt, IotaDID, IotaDocument, IotaIdentityClient, IotaIdentityClientExt,
+        NetworkName,
+    },
     prelude::Resolver,
-    storage::{JwkDocumentExt, JwkMemStore, Storage},
-    verification::{jws::JwsAlgorithm, MethodRelationship, MethodScope},
+    verification::MethodRelationship,
 };
-use identity_stronghold::StrongholdStorage;
 use iota_sdk::{
-    client::{secret::stronghold::StrongholdSecretManager, Client, Password},
-    types::block::{
-        address::Address,
-        output::{AliasOutput, AliasOutputBuilder, RentStructure},
+    client::Client,
+    types::{
+        api::core::response::LedgerInclusionState,
+        block::{
+            address::{Address, ToBech32Ext},
+            input::UtxoInput,
+            output::{
+                unlock_condition::AddressUnlockCondition, AliasId, AliasOutput, AliasOutputBuilder,
+                BasicOutputBuilder, RentStructure,
+            },
+            Block, BlockId,
+        },
     },
 };
 use log::{debug, info, warn};
-// use tokio::time::{sleep, Duration};
+use rand::distributions::{Alphanumeric, DistString};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::time::{sleep, Duration, Instant};
+
+/// How long a block may take to be included by a milestone and its new state to resolve.
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Time between two polls of the block metadata or the resolver.
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Fragment of the RevocationBitmap2022 service of the DIDs.
+const REVOCATION_FRAGMENT: &str = "revocation";
+
+/// Credentials get a random revocation index below this, so the revoked indices are spread over
+/// the bitmap like in a real issuer, which hides how many credentials it issued.
+const REVOCATION_INDEX_RANGE: u32 = 1_000_000;
+
+/// Types of the services added to the DIDs, taken in turn.
+const SERVICE_TYPES: [&str; 2] = ["LinkedDomains", "DIDCommMessaging"];
 
+/// Random lowercase token for service fragments and endpoints.
+fn random_token() -> String {
+    Alphanumeric
+        .sample_string(&mut rand::thread_rng(), 8)
+        .to_lowercase()
+}
+
+/// New service of `did` with a random fragment. The type is taken from `SERVICE_TYPES` by `number`.
+fn new_service(did: &IotaDID, number: usize) -> anyhow::Result<Service> {
+    let service_type = SERVICE_TYPES[number % SERVICE_TYPES.len()];
+    Ok(Service::builder(Default::default())
+        .id(did.to_url().join(format!("#service-{}", random_token()))?)
+        .type_(service_type)
+        .service_endpoint(service_endpoint(service_type)?)
+        .build()?)
+}
+
+/// New endpoint for a service of `service_type`.
+fn service_endpoint(service_type: &str) -> anyhow::Result<Url> {
+    let endpoint = match service_type {
+        "DIDCommMessaging" => format!("https://mediator.example.com/didcomm/{}", random_token()),
+        _ => format!("https://{}.example.com/", random_token()),
+    };
+    Ok(Url::parse(endpoint)?)
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct DIDInformation {
     pub did: IotaDID,
     fragment: String,
+    /// Document before the DID was deactivated, to reactivate it with.
     document: Option<IotaDocument>,
+    /// Services added by the benchmark, the newest one last.
+    #[serde(default)]
+    services: Vec<Service>,
+    /// Id of the service removed last.
+    #[serde(default)]
+    removed_service: Option<DIDUrl>,
+    /// JWT of the last credential the DID issued to itself.
+    #[serde(default)]
+    credential: Option<String>,
+    /// Last presentation of the credential.
+    #[serde(default)]
+    presentation: Option<PresentationInformation>,
+    /// The document has a RevocationBitmap2022 service, so credentials are issued with a status.
+    #[serde(default)]
+    revocation_bitmap: bool,
+    /// Revocation index of the last credential, if it was issued with a status.
+    #[serde(default)]
+    credential_index: Option<u32>,
+    /// The last credential was revoked and not unrevoked since.
+    #[serde(default)]
+    credential_revoked: bool,
+}
+
+/// Presentation created by the holder for the challenge of a verifier.
+#[derive(Clone, Serialize, Deserialize)]
+struct PresentationInformation {
+    jwt: String,
+    challenge: String,
+}
+
+/// DIDs of a `DIDManager` and the stronghold holding their keys, saved to continue
+/// with the same DIDs in a later run.
+#[derive(Serialize, Deserialize)]
+struct DIDManagerState {
+    /// Id of the network the DIDs were published on.
+    network: String,
+    stronghold_path: PathBuf,
+    dids: BTreeMap<usize, DIDInformation>,
+}
+
+impl DIDManagerState {
+    fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("failed to read state file {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("invalid state file {}", path.display()))
+    }
+
+    /// Writes to a temporary file first, so a crash never leaves a truncated state file behind.
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)?;
+        fs::rename(&temp_path, path)
+            .with_context(|| format!("failed to save state file {}", path.display()))
+    }
 }
 
 pub struct DIDManager {
-    client: Client,
-    stronghold_storage: StrongholdStorage,
+    pool: NodePool,
+    /// Node of the pool serving the current action.
+    current_node: usize,
+    keys: KeyStorage,
     address: Address,
     network_name: NetworkName,
-    resolver: Resolver<IotaDocument>,
-    storage: Storage<StrongholdStorage, StrongholdStorage>,
+    /// One resolver per node of the pool.
+    resolvers: Vec<Resolver<IotaDocument>>,
+    retry_policy: RetryPolicy,
+    /// Retries of the last action.
+    retries: Retries,
+    /// Wait until published blocks are included and their new state resolves, timing each phase.
+    confirmation: bool,
+    /// Phases of the last attempt. Behind a mutex so they can be recorded while a DID is borrowed.
+    phases: Mutex<BTreeMap<Phase, f64>>,
+    /// Alias Output published by the last attempt.
+    published_output: Mutex<Option<AliasOutput>>,
+    /// Amount of the changed Alias Output before and after the last attempt.
+    deposit: Mutex<Option<(u64, u64)>>,
+    /// Algorithm of the verification methods that are generated.
+    key_algorithm: KeyAlgorithm,
+    /// Elements added to every new document on top of its first verification method.
+    document_growth: Option<(DocumentGrowth, usize)>,
+    /// Verify the state of a DID after every change. `None` disables the check.
+    consistency: Option<ConsistencyConfig>,
+    network_id: String,
+    /// File the DIDs are saved to. `None` keeps them in memory only.
+    state_file: Option<PathBuf>,
     pub did_map: HashMap<usize, DIDInformation>,
+    /// Index and DID of the last deleted DID, which is no longer in `did_map`.
+    deleted: Option<(usize, IotaDID)>,
 }
 
 impl DIDManager {
-    pub async fn new(api_endpoint: &str, faucet_endpoint: &str) -> anyhow::Result<Self> {
-        info!("Creating new DIDManager");
-
-        // Stronghold snapshot path.
-        let path = random_stronghold_path();
-
-        info!("1111");
+    pub async fn new(
+        network: &IotaTangleNetwork,
+        strategy: NodeStrategy,
+        key_storage: &KeyStorageConfig,
+        backend: StorageBackend,
+    ) -> anyhow::Result<Self> {
+        let keys = match backend {
+            StorageBackend::Stronghold => {
+                KeyStorage::stronghold(key_storage, random_stronghold_path(), true)?
+            }
+            StorageBackend::Memory => KeyStorage::memory()?,
+        };
+        Self::with_keys(network, strategy, keys).await
+    }
 
-        // Stronghold password.
-        let password = Password::from("secure_password".to_owned());
+    /// Continues with the DIDs and the stronghold saved in `state_file`, or starts with a new
+    /// stronghold next to it if it does not exist. Callers save the DIDs with [`DIDManager::save_state`].
+    pub async fn open(
+        network: &IotaTangleNetwork,
+        strategy: NodeStrategy,
+        key_storage: &KeyStorageConfig,
+        backend: StorageBackend,
+        state_file: &Path,
+    ) -> anyhow::Result<Self> {
+        if backend != StorageBackend::Stronghold {
+            bail!("saved DIDs need the stronghold backend to keep their keys");
+        }
 
-        // Create a new client to interact with the IOTA ledger.
-        let client: Client = Client::builder()
-            // .with_local_pow(false)
-            // .with_fallback_to_local_pow(false)
-            .with_primary_node(api_endpoint, None)?
-            .finish()
-            .await?;
+        let state = if state_file.exists() {
+            let state = DIDManagerState::load(state_file)?;
+            if state.network != network.id {
+                bail!(
+                    "state file {} belongs to network {}, not {}",
+                    state_file.display(),
+                    state.network,
+                    network.id
+                );
+            }
+            Some(state)
+        } else {
+            if let Some(parent) = state_file.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            None
+        };
 
-        info!("2222");
+        let stronghold_path = match &state {
+            Some(state) => state.stronghold_path.clone(),
+            None => state_file.with_extension("stronghold"),
+        };
+        let keys = KeyStorage::stronghold(key_storage, stronghold_path, false)?;
+        let mut did_manager = Self::with_keys(network, strategy, keys).await?;
+        if let Some(state) = state {
+            info!(
+                "Loaded {} DIDs from {}",
+                state.dids.len(),
+                state_file.display()
+            );
+            did_manager.did_map = state.dids.into_iter().collect();
+        }
+        did_manager.state_file = Some(state_file.to_path_buf());
+        did_manager.save_state()?;
+        Ok(did_manager)
+    }
 
-        let stronghold = StrongholdSecretManager::builder()
-            .password(password.clone())
-            .build(path.clone())?;
+    async fn with_keys(
+        network: &IotaTangleNetwork,
+        strategy: NodeStrategy,
+        keys: KeyStorage,
+    ) -> anyhow::Result<Self> {
+        info!("Creating new DIDManager");
 
-        // Create a `StrongholdStorage`.
-        // `StrongholdStorage` creates internally a `SecretManager` that can be
-        // referenced to avoid creating multiple instances around the same stronghold snapshot.
-        let stronghold_storage = StrongholdStorage::new(stronghold);
+        // Create one client per node to interact with the IOTA ledger.
+        let pool = NodePool::from_network(network, strategy).await?;
+        let client: &Client = pool.client(0);
 
-        info!("33333");
+        // Make sure the configured endpoints belong to the expected network.
+        network.verify(client).await?;
 
         // Create a DID document.
-        let address: Address = get_address_with_funds(
-            &client,
-            stronghold_storage.as_secret_manager(),
-            faucet_endpoint,
-        )
-        .await?;
+        let address: Address =
+            get_address_with_funds(client, keys.secret_manager(), network.faucet_endpoint())
+                .await?;
 
         let network_name: NetworkName = client.network_name().await?;
 
-        info!("4444 {} {}", network_name, address);
-
-        let storage: Storage<StrongholdStorage, StrongholdStorage> =
-            Storage::new(stronghold_storage.clone(), stronghold_storage.clone());
-
-        // Create resolver
-        let mut resolver = Resolver::<IotaDocument>::new();
-        resolver.attach_iota_handler(client.clone());
-
-        info!("555 local_pow {}", client.get_local_pow().await);
+        // Create resolvers
+        let resolvers = (0..pool.len())
+            .map(|node| {
+                let mut resolver = Resolver::<IotaDocument>::new();
+                resolver.attach_iota_handler(pool.client(node).clone());
+                resolver
+            })
+            .collect();
 
         Ok(Self {
-            client: client,
-            stronghold_storage: stronghold_storage,
-            address: address,
-            network_name: network_name,
-            resolver: resolver,
-            storage: storage,
+            pool,
+            current_node: 0,
+            keys,
+            address,
+            network_name,
+            resolvers,
+            retry_policy: RetryPolicy::default(),
+            retries: Retries::default(),
+            confirmation: false,
+            phases: Mutex::new(BTreeMap::new()),
+            published_output: Mutex::new(None),
+            deposit: Mutex::new(None),
+            deleted: None,
+            key_algorithm: KeyAlgorithm::default(),
+            document_growth: None,
+            consistency: None,
+            network_id: network.id.clone(),
+            state_file: None,
             did_map: HashMap::new(),
         })
     }
 
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_confirmation(mut self, confirmation: bool) -> Self {
+        self.confirmation = confirmation;
+        self
+    }
+
+    pub fn with_consistency_check(mut self, consistency: Option<ConsistencyConfig>) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    pub fn with_key_algorithm(mut self, key_algorithm: KeyAlgorithm) -> Self {
+        self.key_algorithm = key_algorithm;
+        self
+    }
+
+    /// Adds `count` elements of `growth` to every DID document created from now on.
+    pub fn with_document_growth(mut self, growth: DocumentGrowth, count: usize) -> Self {
+        self.document_growth = Some((growth, count));
+        self
+    }
+
     pub fn print_did_if_exist(&mut self, index: usize) {
-        match self.did_map.get(&index) {
-            Some(did_info) => warn!("DID at index {} : {}", index, did_info.did),
+        match self.did(index) {
+            Some(did) => warn!("DID at index {} : {}", index, did),
             None => warn!("No DID found at index {}", index),
         }
     }
 
-    pub async fn run_action(&mut self, action: &Action, index: usize) {
-        match action {
-            Action::CreateDid => {
-                if let Err(e) = self.create_did(index).await {
-                    warn!("Failed to create DID: {:?}", e);
-                }
-            }
-            Action::DeleteDid => {
-                if let Err(e) = self.delete_did(index).await {
-                    warn!("Failed to delete DID: {:?}", e);
-                    self.print_did_if_exist(index);
-                }
+    /// DID created at `index`, if any.
+    pub fn did(&self, index: usize) -> Option<&IotaDID> {
+        self.did_map.get(&index).map(|did_info| &did_info.did)
+    }
+
+    /// URL of the node that served the last action.
+    pub fn current_node(&self) -> &str {
+        self.pool.url(self.current_node)
+    }
+
+    /// Requests served per node of the pool.
+    pub fn node_usage(&self) -> HashMap<String, NodeUsage> {
+        self.pool.usage()
+    }
+
+    /// Retries of the last action.
+    pub fn retries(&self) -> Retries {
+        self.retries
+    }
+
+    /// Duration in seconds of the phases of the last attempt. The phases until the new state
+    /// resolves are only timed with confirmation timing.
+    pub fn phases(&self) -> BTreeMap<Phase, f64> {
+        self.phases.lock().unwrap().clone()
+    }
+
+    /// Size of the Alias Output published by the last attempt. `None` if the action published no document.
+    /// The document is unpacked here, outside of the timed action, to count its services.
+    pub fn output_size(&self) -> Option<OutputSize> {
+        let published_output = self.published_output.lock().unwrap();
+        let alias_output = published_output.as_ref()?;
+        let document = IotaDocument::unpack_from_output(
+            &IotaDID::placeholder(&self.network_name),
+            alias_output,
+            true,
+        )
+        .ok();
+        let revoked = document
+            .as_ref()
+            .and_then(|document| document.resolve_service(REVOCATION_FRAGMENT))
+            .and_then(|service| RevocationBitmap::try_from(service).ok())
+            .map(|bitmap| bitmap.len())
+            .unwrap_or_default();
+        // The state metadata of the Alias Output is the packed DID document.
+        Some(OutputSize {
+            document_size: alias_output.state_metadata().len(),
+            storage_deposit: alias_output.amount(),
+            services: document.map_or(0, |document| document.service().len()),
+            revoked,
+        })
+    }
+
+    /// Tokens the last attempt locked in or reclaimed from the Alias Output of the DID, together with
+    /// the wallet balances queried around the action. `None` if the action changed no Alias Output.
+    pub fn token_cost(
+        &self,
+        balance_before: Option<u64>,
+        balance_after: Option<u64>,
+    ) -> Option<TokenCost> {
+        let (amount_before, amount_after) = (*self.deposit.lock().unwrap())?;
+        Some(TokenCost {
+            amount_before,
+            amount_after,
+            balance_before,
+            balance_after,
+        })
+    }
+
+    /// Balance of the wallet address. `None` if it could not be queried.
+    pub async fn wallet_balance(&self) -> Option<u64> {
+        let client = self.pool.client(self.current_node);
+        let balance = async {
+            let address = self.address.to_bech32(client.get_bech32_hrp().await?);
+            get_address_balance(client, &address).await
+        }
+        .await;
+        match balance {
+            Ok(balance) => Some(balance),
+            Err(e) => {
+                warn!("Failed to query the wallet balance: {:?}", e);
+                None
             }
-            Action::UpdateDid => {
-                if let Err(e) = self.update_did(index).await {
-                    warn!("Failed to update DID: {:?}", e);
-                    self.print_did_if_exist(index);
-                }
+        }
+    }
+
+    fn record_deposit(&self, amount_before: u64, amount_after: u64) {
+        *self.deposit.lock().unwrap() = Some((amount_before, amount_after));
+    }
+
+    /// Adds `duration` to the time spent in `phase` during the current attempt.
+    fn record_phase(&self, phase: Phase, duration: Duration) {
+        *self.phases.lock().unwrap().entry(phase).or_insert(0.0) += duration.as_secs_f64();
+    }
+
+    /// Saves the DIDs to the state file, if there is one.
+    pub fn save_state(&self) -> anyhow::Result<()> {
+        let Some(state_file) = &self.state_file else {
+            return Ok(());
+        };
+        DIDManagerState {
+            network: self.network_id.clone(),
+            stronghold_path: self.keys.path().map(Path::to_path_buf).unwrap_or_default(),
+            dids: self
+                .did_map
+                .iter()
+                .map(|(index, did_info)| (*index, did_info.clone()))
+                .collect(),
+        }
+        .save(state_file)
+    }
+
+    /// Resolves the DID at `index` after a successful `action` until the change is visible.
+    /// `None` if the check is disabled or `action` does not change the DID.
+    pub async fn check_consistency(&self, action: &Action, index: usize) -> Option<Consistency> {
+        let config = self.consistency.as_ref()?;
+        if *action == Action::DeleteDid {
+            let (_, did) = self
+                .deleted
+                .as_ref()
+                .filter(|(deleted, _)| *deleted == index)?;
+            return self
+                .verify_state(action, index, did, ExpectedState::Deleted, config)
+                .await;
+        }
+
+        let did_info = self.did_map.get(&index)?;
+        let expected = match action {
+            Action::CreateDid | Action::UpdateDid => {
+                ExpectedState::HasMethod(did_info.fragment.clone())
             }
-            Action::ResolveDid => {
-                if let Err(e) = self.resolve_did(index).await {
-                    warn!("Failed to resolve DID: {:?}", e);
-                    self.print_did_if_exist(index);
-                }
+            Action::DeactivateDid => ExpectedState::Deactivated,
+            Action::ReactivateDid => ExpectedState::Active,
+            Action::AddService | Action::UpdateService => {
+                ExpectedState::HasService(did_info.services.last()?.clone())
             }
-            Action::DeactivateDid => {
-                if let Err(e) = self.deactivate_did(index).await {
-                    warn!("Failed to deactivate DID: {:?}", e);
-                    self.print_did_if_exist(index);
-                }
+            Action::RemoveService => ExpectedState::NoService(did_info.removed_service.clone()?),
+            Action::AddRevocationBitmap => {
+                ExpectedState::HasRevocationBitmap(REVOCATION_FRAGMENT.to_string())
             }
-            Action::ReactivateDid => {
-                if let Err(e) = self.reactivate_did(index).await {
-                    warn!("Failed to reactivate DID: {:?}", e);
-                    self.print_did_if_exist(index);
-                }
+            Action::RevokeCredential | Action::UnrevokeCredential => ExpectedState::Revocation {
+                fragment: REVOCATION_FRAGMENT.to_string(),
+                credential_index: did_info.credential_index?,
+                revoked: did_info.credential_revoked,
+            },
+            _ => return None,
+        };
+        self.verify_state(action, index, &did_info.did, expected, config)
+            .await
+    }
+
+    async fn verify_state(
+        &self,
+        action: &Action,
+        index: usize,
+        did: &IotaDID,
+        expected: ExpectedState,
+        config: &ConsistencyConfig,
+    ) -> Option<Consistency> {
+        // Read from the next node of the pool to see how fast the change propagates
+        let node = if config.cross_node {
+            (self.current_node + 1) % self.pool.len()
+        } else {
+            self.current_node
+        };
+
+        let consistency = verify_state(
+            &self.resolvers[node],
+            self.pool.url(node),
+            did,
+            &expected,
+            config,
+        )
+        .await;
+        if consistency.inconsistent {
+            warn!(
+                "{} {} not visible on {} after {:.1}s: expected {:?}",
+                index,
+                action.name(),
+                consistency.node,
+                consistency.delay,
+                expected
+            );
+        }
+        Some(consistency)
+    }
+
+    /// Runs `action` on the DID at `index`, retrying it according to the retry policy,
+    /// and classifies the error of the last attempt if it failed.
+    pub async fn run_action(&mut self, action: &Action, index: usize) -> Result<(), FailureKind> {
+        let start = Instant::now();
+        self.retries = Retries::default();
+
+        loop {
+            let result = self.run_attempt(action, index).await;
+            let kind = match result {
+                Ok(()) => return Ok(()),
+                Err(kind) => kind,
+            };
+            let attempt = self.retries.count + 1;
+            if !self.retry_policy.should_retry(attempt, kind) {
+                return Err(kind);
             }
+
+            let backoff = self.retry_policy.backoff(attempt);
+            info!(
+                "{} Retrying {} in {:?} after attempt {} failed ({})",
+                index,
+                action.name(),
+                backoff,
+                attempt,
+                kind
+            );
+            sleep(backoff).await;
+            self.retries.count += 1;
+            self.retries.time = start.elapsed();
+        }
+    }
+
+    /// Runs `action` once on a node selected from the pool.
+    async fn run_attempt(&mut self, action: &Action, index: usize) -> Result<(), FailureKind> {
+        self.current_node = self.pool.select();
+        self.phases.lock().unwrap().clear();
+        *self.published_output.lock().unwrap() = None;
+        *self.deposit.lock().unwrap() = None;
+        let start = Instant::now();
+
+        let result = match action {
+            Action::CreateDid => self.create_did(index).await,
+            Action::DeleteDid => self.delete_did(index).await,
+            Action::UpdateDid => self.update_did(index).await,
+            Action::ResolveDid => self.resolve_did(index).await,
+            Action::DeactivateDid => self.deactivate_did(index).await,
+            Action::ReactivateDid => self.reactivate_did(index).await,
+            Action::AddService => self.add_service(index).await,
+            Action::UpdateService => self.update_service(index).await,
+            Action::RemoveService => self.remove_service(index).await,
+            Action::IssueCredential => self.issue_credential(index).await,
+            Action::VerifyCredential => self.verify_credential(index).await,
+            Action::CreatePresentation => self.create_presentation(index).await,
+            Action::VerifyPresentation => self.verify_presentation(index).await,
+            Action::AddRevocationBitmap => self.add_revocation_bitmap(index).await,
+            Action::RevokeCredential => self.set_credential_revoked(index, true).await,
+            Action::UnrevokeCredential => self.set_credential_revoked(index, false).await,
+            Action::CheckCredentialStatus => self.check_credential_status(index).await,
             _ => {
                 // Do nothing
+                return Ok(());
             }
-        }
+        };
+
+        self.pool
+            .record(self.current_node, start.elapsed(), result.is_ok());
+
+        result.map_err(|e| {
+            let kind = FailureKind::classify(&e);
+            warn!(
+                "{} failed on {} ({}): {:?}",
+                action.name(),
+                self.current_node(),
+                kind,
+                e
+            );
+            if *action != Action::CreateDid {
+                self.print_did_if_exist(index);
+            }
+            kind
+        })
     }
 
     pub async fn create_did(&mut self, index: usize) -> anyhow::Result<()> {
@@ -160,31 +627,32 @@ impl DIDManager {
         let mut document = IotaDocument::new(&self.network_name);
 
         // Generates a verification method. This will store the key-id as well as the private key
-        // in the stronghold file.
-        let fragment = document
-            .generate_method(
-                &self.storage,
-                JwkMemStore::ED25519_KEY_TYPE,
-                JwsAlgorithm::EdDSA,
-                None,
-                MethodScope::VerificationMethod,
-            )
+        // in the key storage.
+        let start = Instant::now();
+        let fragment = self
+            .keys
+            .generate_method(&mut document, self.key_algorithm)
             .await?;
+        self.record_phase(Phase::KeyStorage, start.elapsed());
+
+        if let Some((growth, count)) = self.document_growth {
+            self.grow_document(&mut document, growth, count).await?;
+        }
+        check_document_size(&document)?;
 
         // Construct an Alias Output containing the DID document, with the wallet address
         // set as both the state controller and governor.
         let alias_output: AliasOutput = self
-            .client
+            .pool
+            .client(self.current_node)
             .new_did_output(self.address, document, None)
             .await?;
+        self.record_deposit(0, alias_output.amount());
 
         // info!("Alias output: {alias_output:?}");
 
         // Publish the Alias Output and get the published DID document.
-        let document: IotaDocument = self
-            .client
-            .publish_did_output(self.stronghold_storage.as_secret_manager(), alias_output)
-            .await?;
+        let document: IotaDocument = self.publish_did_output(alias_output).await?;
 
         info!("DID created: {}", document.id());
 
@@ -194,6 +662,13 @@ impl DIDManager {
                 did: document.id().clone(),
                 fragment: fragment,
                 document: None,
+                services: vec![],
+                removed_service: None,
+                credential: None,
+                presentation: None,
+                revocation_bitmap: false,
+                credential_index: None,
+                credential_revoked: false,
             },
         );
 
@@ -203,22 +678,21 @@ impl DIDManager {
     pub async fn update_did(&mut self, index: usize) -> anyhow::Result<()> {
         info!("{} Updating DID", index);
 
-        match self.did_map.get_mut(&index) {
+        let new_fragment = match self.did_map.get(&index) {
             Some(did_info) => {
                 // Resolve the latest state of the document.
-                let mut document: IotaDocument = self.resolver.resolve(&did_info.did).await?;
-
-                // Insert a new Ed25519 verification method in the DID document.
-                let new_fragment: String = document
-                    .generate_method(
-                        &self.storage,
-                        JwkMemStore::ED25519_KEY_TYPE,
-                        JwsAlgorithm::EdDSA,
-                        None,
-                        MethodScope::VerificationMethod,
-                    )
+                let mut document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
                     .await?;
 
+                // Insert a new verification method in the DID document.
+                let start = Instant::now();
+                let new_fragment: String = self
+                    .keys
+                    .generate_method(&mut document, self.key_algorithm)
+                    .await?;
+                self.record_phase(Phase::KeyStorage, start.elapsed());
+
                 // Attach a new method relationship to the inserted method.
                 document.attach_method_relationship(
                     &document.id().to_url().join(format!("#{new_fragment}"))?,
@@ -230,37 +704,54 @@ impl DIDManager {
                 // Remove a verification method.
                 let original_method: DIDUrl = document
                     .resolve_method(did_info.fragment.as_str(), None)
-                    .unwrap()
+                    .ok_or_else(|| {
+                        ResolutionMismatch(format!(
+                            "method #{} not found in {}",
+                            did_info.fragment,
+                            document.id()
+                        ))
+                    })?
                     .id()
                     .clone();
-                document
-                    .purge_method(&self.storage, &original_method)
-                    .await
-                    .unwrap();
+                let start = Instant::now();
+                self.keys
+                    .purge_method(&mut document, &original_method)
+                    .await?;
+                self.record_phase(Phase::KeyStorage, start.elapsed());
 
                 // Resolve the latest output and update it with the given document.
-                let alias_output: AliasOutput =
-                    self.client.update_did_output(document.clone()).await?;
+                check_document_size(&document)?;
+                let alias_output: AliasOutput = self
+                    .pool
+                    .client(self.current_node)
+                    .update_did_output(document.clone())
+                    .await?;
 
                 // Because the size of the DID document increased, we have to increase the allocated storage deposit.
                 // This increases the deposit amount to the new minimum.
-                let rent_structure: RentStructure = self.client.get_rent_structure().await?;
+                let rent_structure: RentStructure = self
+                    .pool
+                    .client(self.current_node)
+                    .get_rent_structure()
+                    .await?;
+                let amount_before = alias_output.amount();
                 let alias_output: AliasOutput = AliasOutputBuilder::from(&alias_output)
                     .with_minimum_storage_deposit(rent_structure)
                     .finish()?;
+                self.record_deposit(amount_before, alias_output.amount());
 
                 // Publish the updated Alias Output.
-                let updated: IotaDocument = self
-                    .client
-                    .publish_did_output(self.stronghold_storage.as_secret_manager(), alias_output)
-                    .await?;
+                let updated: IotaDocument = self.publish_did_output(alias_output).await?;
                 debug!("Updated DID: {}", updated.id());
 
-                did_info.fragment = new_fragment;
+                new_fragment
             }
-            None => return Err(anyhow!("No object found at index {}", index)),
-        }
+            None => return Err(MissingDid(index).into()),
+        };
 
+        if let Some(did_info) = self.did_map.get_mut(&index) {
+            did_info.fragment = new_fragment;
+        }
         Ok(())
     }
 
@@ -272,12 +763,21 @@ impl DIDManager {
 
         match self.did_map.get(&index) {
             Some(did_info) => {
-                let resolved_document: IotaDocument = self.resolver.resolve(&did_info.did).await?;
-                assert_eq!(did_info.did, *resolved_document.id());
+                let resolved_document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
+                    .await?;
+                if did_info.did != *resolved_document.id() {
+                    return Err(ResolutionMismatch(format!(
+                        "resolved {} instead of {}",
+                        resolved_document.id(),
+                        did_info.did
+                    ))
+                    .into());
+                }
 
                 debug!("The did resolved is: {}", did_info.did);
             }
-            None => return Err(anyhow!("No object found at index {}", index)),
+            None => return Err(MissingDid(index).into()),
         }
         Ok(())
     }
@@ -288,36 +788,39 @@ impl DIDManager {
     pub async fn deactivate_did(&mut self, index: usize) -> anyhow::Result<()> {
         info!("{} Deactivating DID", index);
 
-        match self.did_map.get_mut(&index) {
+        let resolved_document = match self.did_map.get(&index) {
             Some(did_info) => {
-                let resolved_document: IotaDocument = self.resolver.resolve(&did_info.did).await?;
-
-                did_info.document = Some(resolved_document);
+                let resolved_document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
+                    .await?;
 
                 // Deactivate the DID by publishing an empty document.
                 // This process can be reversed since the Alias Output is not destroyed.
                 // Deactivation may only be performed by the state controller of the Alias Output.
-                let deactivated_output: AliasOutput =
-                    self.client.deactivate_did_output(&did_info.did).await?;
+                let deactivated_output: AliasOutput = self
+                    .pool
+                    .client(self.current_node)
+                    .deactivate_did_output(&did_info.did)
+                    .await?;
 
                 // Optional: reduce and reclaim the storage deposit, sending the tokens to the state controller.
-                let rent_structure = self.client.get_rent_structure().await?;
+                let rent_structure = self
+                    .pool
+                    .client(self.current_node)
+                    .get_rent_structure()
+                    .await?;
+                let amount_before = deactivated_output.amount();
                 let deactivated_output = AliasOutputBuilder::from(&deactivated_output)
                     .with_minimum_storage_deposit(rent_structure)
                     .finish()?;
+                self.record_deposit(amount_before, deactivated_output.amount());
 
                 // Publish the deactivated DID document.
-                let _ = self
-                    .client
-                    .publish_did_output(
-                        self.stronghold_storage.as_secret_manager(),
-                        deactivated_output,
-                    )
-                    .await?;
+                let _ = self.publish_did_output(deactivated_output).await?;
 
                 // // Resolving a deactivated DID returns an empty DID document
                 // // with its `deactivated` metadata field set to `true`.
-                // let deactivated: IotaDocument = self.resolver.resolve(&did_info.did).await?;
+                // let deactivated: IotaDocument = self.resolvers[self.current_node].resolve(&did_info.did).await?;
 
                 // if deactivated.metadata.deactivated != Some(true) {
                 //     return Err(anyhow::anyhow!(
@@ -326,8 +829,15 @@ impl DIDManager {
                 //     ));
                 // }
                 // debug!("Deactivated DID document: {deactivated:#}");
+
+                resolved_document
             }
-            None => return Err(anyhow!("No object found at index {}", index)),
+            None => return Err(MissingDid(index).into()),
+        };
+
+        // Keep the document to reactivate the DID with.
+        if let Some(did_info) = self.did_map.get_mut(&index) {
+            did_info.document = Some(resolved_document);
         }
         Ok(())
     }
@@ -338,29 +848,33 @@ impl DIDManager {
     pub async fn reactivate_did(&mut self, index: usize) -> anyhow::Result<()> {
         info!("{} Reactivating DID", index);
 
-        match self.did_map.get_mut(&index) {
+        match self.did_map.get(&index) {
             Some(did_info) => {
                 match &did_info.document {
                     Some(document) => {
                         // Re-activate the DID by publishing a valid DID document.
-                        let reactivated_output: AliasOutput =
-                            self.client.update_did_output(document.clone()).await?;
+                        let reactivated_output: AliasOutput = self
+                            .pool
+                            .client(self.current_node)
+                            .update_did_output(document.clone())
+                            .await?;
 
                         // Increase the storage deposit to the minimum again, if it was reclaimed during deactivation.
-                        let rent_structure = self.client.get_rent_structure().await?;
+                        let rent_structure = self
+                            .pool
+                            .client(self.current_node)
+                            .get_rent_structure()
+                            .await?;
+                        let amount_before = reactivated_output.amount();
                         let reactivated_output = AliasOutputBuilder::from(&reactivated_output)
                             .with_minimum_storage_deposit(rent_structure)
                             .finish()?;
-                        self.client
-                            .publish_did_output(
-                                self.stronghold_storage.as_secret_manager(),
-                                reactivated_output,
-                            )
-                            .await?;
+                        self.record_deposit(amount_before, reactivated_output.amount());
+                        self.publish_did_output(reactivated_output).await?;
 
                         // // Resolve the reactivated DID document.
                         // let reactivated: IotaDocument =
-                        //     self.resolver.resolve(&did_info.did).await?;
+                        //     self.resolvers[self.current_node].resolve(&did_info.did).await?;
                         // // assert_eq!(*document, reactivated);
 
                         // if reactivated.metadata.deactivated.unwrap_or_default() {
@@ -375,16 +889,457 @@ impl DIDManager {
                     None => return Err(anyhow!("DID was never deactivated {}", did_info.did)),
                 }
             }
-            None => return Err(anyhow!("No object found at index {}", index)),
+            None => return Err(MissingDid(index).into()),
+        }
+
+        Ok(())
+    }
+
+    /// Adds a service to the DID, alternating between LinkedDomains and DIDComm messaging endpoints.
+    pub async fn add_service(&mut self, index: usize) -> anyhow::Result<()> {
+        info!("{} Adding service", index);
+
+        let service = match self.did_map.get(&index) {
+            Some(did_info) => {
+                let mut document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
+                    .await?;
+
+                let service = new_service(document.id(), did_info.services.len())?;
+                document.insert_service(service.clone())?;
+                document.metadata.updated = Some(Timestamp::now_utc());
+
+                self.publish_document(document).await?;
+                service
+            }
+            None => return Err(MissingDid(index).into()),
+        };
+
+        if let Some(did_info) = self.did_map.get_mut(&index) {
+            did_info.services.push(service);
+        }
+        Ok(())
+    }
+
+    /// Points the newest service added to the DID to a new endpoint.
+    pub async fn update_service(&mut self, index: usize) -> anyhow::Result<()> {
+        info!("{} Updating service", index);
+
+        let service = match self.did_map.get(&index) {
+            Some(did_info) => {
+                let old_service = did_info.services.last().ok_or(MissingService(index))?;
+                let mut document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
+                    .await?;
+
+                document.remove_service(old_service.id()).ok_or_else(|| {
+                    ResolutionMismatch(format!(
+                        "service {} not found in {}",
+                        old_service.id(),
+                        document.id()
+                    ))
+                })?;
+                let mut builder = Service::builder(Default::default()).id(old_service.id().clone());
+                for service_type in old_service.type_().iter() {
+                    builder = builder.type_(service_type.clone());
+                }
+                let service_type = old_service.type_().iter().next().map_or("", String::as_str);
+                let service = builder
+                    .service_endpoint(service_endpoint(service_type)?)
+                    .build()?;
+                document.insert_service(service.clone())?;
+                document.metadata.updated = Some(Timestamp::now_utc());
+
+                self.publish_document(document).await?;
+                service
+            }
+            None => return Err(MissingDid(index).into()),
+        };
+
+        if let Some(did_info) = self.did_map.get_mut(&index) {
+            did_info.services.pop();
+            did_info.services.push(service);
+        }
+        Ok(())
+    }
+
+    /// Removes the newest service added to the DID.
+    pub async fn remove_service(&mut self, index: usize) -> anyhow::Result<()> {
+        info!("{} Removing service", index);
+
+        match self.did_map.get(&index) {
+            Some(did_info) => {
+                let service = did_info.services.last().ok_or(MissingService(index))?;
+                let mut document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
+                    .await?;
+
+                document.remove_service(service.id()).ok_or_else(|| {
+                    ResolutionMismatch(format!(
+                        "service {} not found in {}",
+                        service.id(),
+                        document.id()
+                    ))
+                })?;
+                document.metadata.updated = Some(Timestamp::now_utc());
+
+                self.publish_document(document).await?;
+            }
+            None => return Err(MissingDid(index).into()),
         }
 
+        if let Some(did_info) = self.did_map.get_mut(&index) {
+            did_info.removed_service = did_info.services.pop().map(|service| service.id().clone());
+        }
+        Ok(())
+    }
+
+    /// Adds `count` verification methods, services or custom properties to `document`.
+    async fn grow_document(
+        &self,
+        document: &mut IotaDocument,
+        growth: DocumentGrowth,
+        count: usize,
+    ) -> anyhow::Result<()> {
+        match growth {
+            DocumentGrowth::Methods => {
+                let start = Instant::now();
+                for _ in 0..count {
+                    self.keys
+                        .generate_method(document, self.key_algorithm)
+                        .await?;
+                }
+                self.record_phase(Phase::KeyStorage, start.elapsed());
+            }
+            DocumentGrowth::Services => {
+                for number in 0..count {
+                    let service = new_service(document.id(), number)?;
+                    document.insert_service(service)?;
+                }
+            }
+            DocumentGrowth::Properties => {
+                for number in 0..count {
+                    document.properties_mut_unchecked().insert(
+                        format!("property{}", number),
+                        Value::String(random_property(PROPERTY_SIZE)),
+                    );
+                }
+            }
+        }
         Ok(())
     }
 
+    /// Issues a JWT credential from the DID to itself, signed with the key of its current method.
+    /// The issuer resolves its latest document first.
+    pub async fn issue_credential(&mut self, index: usize) -> anyhow::Result<()> {
+        info!("{} Issuing credential", index);
+
+        let (jwt, credential_index) = match self.did_map.get(&index) {
+            Some(did_info) => {
+                let document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
+                    .await?;
+
+                let subject = Subject::from_json_value(serde_json::json!({
+                    "id": document.id().as_str(),
+                    "name": format!("Subject {}", index),
+                    "degree": {
+                        "type": "BachelorDegree",
+                        "name": "Bachelor of Science and Arts",
+                    },
+                }))?;
+                let mut builder = CredentialBuilder::default()
+                    .id(Url::parse(format!(
+                        "https://example.com/credentials/{}",
+                        random_token()
+                    ))?)
+                    .issuer(Url::parse(document.id().as_str())?)
+                    .type_("UniversityDegreeCredential")
+                    .subject(subject);
+
+                // With a revocation bitmap the credential can be revoked through its index
+                let credential_index = did_info
+                    .revocation_bitmap
+                    .then(|| rand::thread_rng().gen_range(0..REVOCATION_INDEX_RANGE));
+                if let Some(credential_index) = credential_index {
+                    let service_url = document
+                        .id()
+                        .to_url()
+                        .join(format!("#{REVOCATION_FRAGMENT}"))?;
+                    builder =
+                        builder.status(RevocationBitmapStatus::new(service_url, credential_index));
+                }
+                let credential: Credential = builder.build()?;
+
+                let jwt = self
+                    .keys
+                    .create_credential_jwt(&document, &did_info.fragment, &credential)
+                    .await?;
+                (jwt, credential_index)
+            }
+            None => return Err(MissingDid(index).into()),
+        };
+
+        if let Some(did_info) = self.did_map.get_mut(&index) {
+            did_info.credential = Some(jwt.as_str().to_string());
+            did_info.credential_index = credential_index;
+            did_info.credential_revoked = false;
+        }
+        Ok(())
+    }
+
+    /// Validates the last credential of the DID against the resolved document of its issuer.
+    pub async fn verify_credential(&self, index: usize) -> anyhow::Result<()> {
+        info!("{} Verifying credential", index);
+
+        let did_info = self.did_map.get(&index).ok_or(MissingDid(index))?;
+        let jwt = Jwt::new(
+            did_info
+                .credential
+                .clone()
+                .ok_or(MissingCredential(index))?,
+        );
+
+        let issuer: IotaDID = JwtCredentialValidatorUtils::extract_issuer_from_jwt(&jwt)?;
+        let issuer_document: IotaDocument =
+            self.resolvers[self.current_node].resolve(&issuer).await?;
+        JwtCredentialValidator::with_signature_verifier(EdDSAJwsVerifier::default())
+            .validate::<_, Object>(
+                &jwt,
+                &issuer_document,
+                &JwtCredentialValidationOptions::default(),
+                FailFast::FirstError,
+            )
+            .map_err(|e| InvalidCredential(e.to_string()))?;
+        Ok(())
+    }
+
+    /// Presents the last credential of the DID to a verifier, answering a random challenge.
+    pub async fn create_presentation(&mut self, index: usize) -> anyhow::Result<()> {
+        info!("{} Creating presentation", index);
+
+        let presentation = match self.did_map.get(&index) {
+            Some(did_info) => {
+                let credential = did_info
+                    .credential
+                    .clone()
+                    .ok_or(MissingCredential(index))?;
+                let document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
+                    .await?;
+
+                let presentation: Presentation<Jwt> =
+                    PresentationBuilder::new(document.id().to_url().into(), Default::default())
+                        .credential(Jwt::new(credential))
+                        .build()?;
+                let challenge = random_token();
+                let jwt = self
+                    .keys
+                    .create_presentation_jwt(
+                        &document,
+                        &did_info.fragment,
+                        &presentation,
+                        &challenge,
+                    )
+                    .await?;
+
+                PresentationInformation {
+                    jwt: jwt.as_str().to_string(),
+                    challenge,
+                }
+            }
+            None => return Err(MissingDid(index).into()),
+        };
+
+        if let Some(did_info) = self.did_map.get_mut(&index) {
+            did_info.presentation = Some(presentation);
+        }
+        Ok(())
+    }
+
+    /// Validates the last presentation of the DID and the credentials in it, resolving the holder
+    /// and the issuers like a verifier would.
+    pub async fn verify_presentation(&self, index: usize) -> anyhow::Result<()> {
+        info!("{} Verifying presentation", index);
+
+        let did_info = self.did_map.get(&index).ok_or(MissingDid(index))?;
+        let presentation = did_info
+            .presentation
+            .as_ref()
+            .ok_or(MissingCredential(index))?;
+        let jwt = Jwt::new(presentation.jwt.clone());
+        let resolver = &self.resolvers[self.current_node];
+
+        let holder: IotaDID = JwtPresentationValidatorUtils::extract_holder(&jwt)?;
+        let holder_document: IotaDocument = resolver.resolve(&holder).await?;
+        let options = JwtPresentationValidationOptions::default().presentation_verifier_options(
+            JwsVerificationOptions::default().nonce(presentation.challenge.clone()),
+        );
+        let decoded: DecodedJwtPresentation<Jwt> =
+            JwtPresentationValidator::with_signature_verifier(EdDSAJwsVerifier::default())
+                .validate(&jwt, &holder_document, &options)
+                .map_err(|e| InvalidCredential(e.to_string()))?;
+
+        let credentials = &decoded.presentation.verifiable_credential;
+        let issuers: Vec<IotaDID> = credentials
+            .iter()
+            .map(JwtCredentialValidatorUtils::extract_issuer_from_jwt)
+            .collect::<Result<_, _>>()?;
+        let issuer_documents: HashMap<IotaDID, IotaDocument> =
+            resolver.resolve_multiple(&issuers).await?;
+
+        let validator =
+            JwtCredentialValidator::with_signature_verifier(EdDSAJwsVerifier::default());
+        let options = JwtCredentialValidationOptions::default().subject_holder_relationship(
+            holder.to_url().into(),
+            SubjectHolderRelationship::AlwaysSubject,
+        );
+        for (credential, issuer) in credentials.iter().zip(&issuers) {
+            let issuer_document = issuer_documents
+                .get(issuer)
+                .ok_or_else(|| ResolutionMismatch(format!("issuer {} was not resolved", issuer)))?;
+            validator
+                .validate::<_, Object>(credential, issuer_document, &options, FailFast::FirstError)
+                .map_err(|e| InvalidCredential(e.to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Adds an empty RevocationBitmap2022 service to the DID. Credentials issued afterwards carry a
+    /// status pointing to it.
+    pub async fn add_revocation_bitmap(&mut self, index: usize) -> anyhow::Result<()> {
+        info!("{} Adding revocation bitmap", index);
+
+        match self.did_map.get(&index) {
+            Some(did_info) => {
+                let mut document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
+                    .await?;
+
+                let service_url = document
+                    .id()
+                    .to_url()
+                    .join(format!("#{REVOCATION_FRAGMENT}"))?;
+                document.insert_service(RevocationBitmap::new().to_service(service_url)?)?;
+                document.metadata.updated = Some(Timestamp::now_utc());
+
+                self.publish_document(document).await?;
+            }
+            None => return Err(MissingDid(index).into()),
+        }
+
+        if let Some(did_info) = self.did_map.get_mut(&index) {
+            did_info.revocation_bitmap = true;
+        }
+        Ok(())
+    }
+
+    /// Revokes or unrevokes the last credential of the DID by updating the revocation bitmap
+    /// in its document.
+    pub async fn set_credential_revoked(
+        &mut self,
+        index: usize,
+        revoked: bool,
+    ) -> anyhow::Result<()> {
+        info!(
+            "{} {} credential",
+            index,
+            if revoked { "Revoking" } else { "Unrevoking" }
+        );
+
+        match self.did_map.get(&index) {
+            Some(did_info) => {
+                let credential_index = did_info.credential_index.ok_or(MissingCredential(index))?;
+                let mut document: IotaDocument = self.resolvers[self.current_node]
+                    .resolve(&did_info.did)
+                    .await?;
+
+                if revoked {
+                    document.revoke_credentials(REVOCATION_FRAGMENT, &[credential_index])?;
+                } else {
+                    document.unrevoke_credentials(REVOCATION_FRAGMENT, &[credential_index])?;
+                }
+                document.metadata.updated = Some(Timestamp::now_utc());
+
+                self.publish_document(document).await?;
+            }
+            None => return Err(MissingDid(index).into()),
+        }
+
+        if let Some(did_info) = self.did_map.get_mut(&index) {
+            did_info.credential_revoked = revoked;
+        }
+        Ok(())
+    }
+
+    /// Checks the status of the last credential of the DID through the resolved issuer document,
+    /// like a verifier would. Fails if the status differs from the last revocation of the benchmark.
+    pub async fn check_credential_status(&self, index: usize) -> anyhow::Result<()> {
+        info!("{} Checking credential status", index);
+
+        let did_info = self.did_map.get(&index).ok_or(MissingDid(index))?;
+        if did_info.credential_index.is_none() {
+            return Err(MissingCredential(index).into());
+        }
+        let jwt = Jwt::new(
+            did_info
+                .credential
+                .clone()
+                .ok_or(MissingCredential(index))?,
+        );
+
+        let issuer: IotaDID = JwtCredentialValidatorUtils::extract_issuer_from_jwt(&jwt)?;
+        let issuer_document: IotaDocument =
+            self.resolvers[self.current_node].resolve(&issuer).await?;
+        let decoded = JwtCredentialValidator::with_signature_verifier(EdDSAJwsVerifier::default())
+            .validate::<_, Object>(
+                &jwt,
+                &issuer_document,
+                &JwtCredentialValidationOptions::default().status_check(StatusCheck::SkipAll),
+                FailFast::FirstError,
+            )
+            .map_err(|e| InvalidCredential(e.to_string()))?;
+
+        // Only a revoked status counts, a missing or malformed bitmap is a failure
+        let revoked = match JwtCredentialValidatorUtils::check_status(
+            &decoded.credential,
+            &[issuer_document],
+            StatusCheck::Strict,
+        ) {
+            Ok(()) => false,
+            Err(JwtValidationError::Revoked) => true,
+            Err(e) => return Err(InvalidCredential(e.to_string()).into()),
+        };
+        if revoked != did_info.credential_revoked {
+            return Err(ResolutionMismatch(format!(
+                "credential of {} is {}revoked, expected the opposite",
+                did_info.did,
+                if revoked { "" } else { "not " }
+            ))
+            .into());
+        }
+        Ok(())
+    }
+
+    /// Publishes `document` as the new state of its Alias Output, adjusting the storage deposit
+    /// to the minimum for the new size.
+    async fn publish_document(&self, document: IotaDocument) -> anyhow::Result<IotaDocument> {
+        check_document_size(&document)?;
+        let client = self.pool.client(self.current_node);
+        let alias_output: AliasOutput = client.update_did_output(document).await?;
+        let rent_structure: RentStructure = client.get_rent_structure().await?;
+        let amount_before = alias_output.amount();
+        let alias_output: AliasOutput = AliasOutputBuilder::from(&alias_output)
+            .with_minimum_storage_deposit(rent_structure)
+            .finish()?;
+        self.record_deposit(amount_before, alias_output.amount());
+        self.publish_did_output(alias_output).await
+    }
+
     ///
     ///
     ///
-    pub async fn delete_did(&self, index: usize) -> anyhow::Result<()> {
+    pub async fn delete_did(&mut self, index: usize) -> anyhow::Result<()> {
         info!("{} Deleting DID", index);
 
         match self.did_map.get(&index) {
@@ -392,18 +1347,24 @@ impl DIDManager {
                 // Deletes the Alias Output and its contained DID Document, rendering the DID permanently destroyed.
                 // This operation is *not* reversible.
                 // Deletion can only be done by the governor of the Alias Output.
-                self.client
-                    .delete_did_output(
-                        self.stronghold_storage.as_secret_manager(),
-                        self.address,
-                        &did_info.did,
-                    )
-                    .await?;
+                if self.confirmation {
+                    self.delete_did_output_confirmed(&did_info.did).await?;
+                } else {
+                    let client = self.pool.client(self.current_node);
+                    // The whole deposit returns to the wallet
+                    let (_, alias_output) = client
+                        .get_alias_output(AliasId::from(&did_info.did))
+                        .await?;
+                    client
+                        .delete_did_output(self.keys.secret_manager(), self.address, &did_info.did)
+                        .await?;
+                    self.record_deposit(alias_output.amount(), 0);
+                }
 
                 // // Attempting to resolve a deleted DID results in a `NoOutput` error.
                 // let mut attempts = 0;
                 // while attempts < 5 {
-                //     match self.client.resolve_did(&did_info.did).await {
+                //     match self.pool.client(self.current_node).resolve_did(&did_info.did).await {
                 //         Ok(_) => {
                 //             sleep(Duration::from_millis(10)).await;
                 //             attempts += 1;
@@ -428,8 +1389,161 @@ impl DIDManager {
                 // }
                 // return Err(anyhow!("DID was not deleted {}", did_info.did));
             }
-            None => return Err(anyhow!("No object found at index {}", index)),
+            None => return Err(MissingDid(index).into()),
+        }
+
+        // The DID no longer exists, so it is not saved or used by later actions
+        if let Some(did_info) = self.did_map.remove(&index) {
+            self.deleted = Some((index, did_info.did));
         }
         Ok(())
     }
+
+    /// Publishes the Alias Output of a DID and returns the published document. With confirmation
+    /// timing, the submission, the milestone inclusion and the resolution of the new document are
+    /// awaited and timed one after the other.
+    async fn publish_did_output(&self, alias_output: AliasOutput) -> anyhow::Result<IotaDocument> {
+        *self.published_output.lock().unwrap() = Some(alias_output.clone());
+
+        let client = self.pool.client(self.current_node);
+        if !self.confirmation {
+            return Ok(client
+                .publish_did_output(self.keys.secret_manager(), alias_output)
+                .await?);
+        }
+
+        let start = Instant::now();
+        let block: Block = client
+            .build_block()
+            .with_secret_manager(self.keys.secret_manager())
+            .with_outputs(vec![alias_output.into()])?
+            .finish()
+            .await?;
+        self.record_phase(Phase::Submit, start.elapsed());
+
+        let start = Instant::now();
+        self.wait_for_inclusion(&block.id()).await?;
+        self.record_phase(Phase::Inclusion, start.elapsed());
+
+        let document = IotaDocument::unpack_from_block(&self.network_name, &block)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("block {} contains no DID document", block.id()))?;
+
+        let start = Instant::now();
+        self.wait_for_resolution(document.id(), Some(&document))
+            .await?;
+        self.record_phase(Phase::Resolution, start.elapsed());
+
+        Ok(document)
+    }
+
+    /// Deletes the Alias Output of `did` like `delete_did_output`, timing the phases until the DID
+    /// no longer resolves.
+    async fn delete_did_output_confirmed(&self, did: &IotaDID) -> anyhow::Result<()> {
+        let client = self.pool.client(self.current_node);
+
+        let start = Instant::now();
+        let (output_id, alias_output) = client.get_alias_output(AliasId::from(did)).await?;
+        // Return the tokens of the Alias Output to the governor.
+        self.record_deposit(alias_output.amount(), 0);
+        let basic_output = BasicOutputBuilder::new_with_amount(alias_output.amount())
+            .with_native_tokens(alias_output.native_tokens().clone())
+            .add_unlock_condition(AddressUnlockCondition::new(self.address))
+            .finish_output(client.get_token_supply().await?)?;
+        let block: Block = client
+            .build_block()
+            .with_secret_manager(self.keys.secret_manager())
+            .with_input(UtxoInput::from(output_id))?
+            .with_outputs(vec![basic_output])?
+            .finish()
+            .await?;
+        self.record_phase(Phase::Submit, start.elapsed());
+
+        let start = Instant::now();
+        self.wait_for_inclusion(&block.id()).await?;
+        self.record_phase(Phase::Inclusion, start.elapsed());
+
+        let start = Instant::now();
+        self.wait_for_resolution(did, None).await?;
+        self.record_phase(Phase::Resolution, start.elapsed());
+
+        Ok(())
+    }
+
+    /// Polls the metadata of `block_id` until a milestone included it.
+    async fn wait_for_inclusion(&self, block_id: &BlockId) -> anyhow::Result<()> {
+        let deadline = Instant::now() + CONFIRMATION_TIMEOUT;
+        while Instant::now() < deadline {
+            let metadata = self
+                .pool
+                .client(self.current_node)
+                .get_block_metadata(block_id)
+                .await?;
+            match metadata.ledger_inclusion_state {
+                Some(LedgerInclusionState::Included)
+                | Some(LedgerInclusionState::NoTransaction) => return Ok(()),
+                Some(LedgerInclusionState::Conflicting) => {
+                    return Err(ConflictingBlock(block_id.to_string()).into())
+                }
+                None => sleep(CONFIRMATION_POLL_INTERVAL).await,
+            }
+        }
+        Err(ConfirmationTimeout(format!(
+            "block {} was not included within {:?}",
+            block_id, CONFIRMATION_TIMEOUT
+        ))
+        .into())
+    }
+
+    /// Resolves `did` until it returns the `expected` document, or until it no longer resolves
+    /// if `expected` is `None`.
+    async fn wait_for_resolution(
+        &self,
+        did: &IotaDID,
+        expected: Option<&IotaDocument>,
+    ) -> anyhow::Result<()> {
+        let deadline = Instant::now() + CONFIRMATION_TIMEOUT;
+        while Instant::now() < deadline {
+            let resolved = self.resolvers[self.current_node]
+                .resolve(did)
+                .await
+                .map_err(anyhow::Error::from);
+            let done = match (&resolved, expected) {
+                (Ok(document), Some(expected)) => same_state(document, expected),
+                (Err(_), None) => ExpectedState::Deleted.is_met(&resolved),
+                _ => false,
+            };
+            if done {
+                return Ok(());
+            }
+            sleep(CONFIRMATION_POLL_INTERVAL).await;
+        }
+        Err(ConfirmationTimeout(format!(
+            "{} did not resolve to its new state within {:?}",
+            did, CONFIRMATION_TIMEOUT
+        ))
+        .into())
+    }
+}
+
+/// Whether two copies of a document have the state the DID actions change: the id, the
+/// verification methods, the services and the deactivated flag. The metadata of a resolved
+/// document, like its timestamps, can differ from the published copy and is not compared.
+/// Fails with [`DocumentTooLarge`] if the packed `document` does not fit into the state metadata
+/// of an Alias Output, which the node would reject.
+fn check_document_size(document: &IotaDocument) -> anyhow::Result<()> {
+    let size = document.clone().pack()?.len();
+    if size > AliasOutput::STATE_METADATA_LENGTH_MAX as usize {
+        return Err(DocumentTooLarge(size).into());
+    }
+    Ok(())
+}
+
+fn same_state(document: &IotaDocument, other: &IotaDocument) -> bool {
+    document.id() == other.id()
+        && document.methods(None) == other.methods(None)
+        && document.service() == other.service()
+        && document.metadata.deactivated.unwrap_or(false)
+            == other.metadata.deactivated.unwrap_or(false)
 }
@@ -0,0 +1,201 @@
+use chrono::Utc;
+use clap::ValueEnum;
+use log::{info, warn};
+use rand::distributions::{Alphanumeric, DistString};
+use serde::{Deserialize, Serialize};
+use serde_json::to_string_pretty;
+use tokio::time::Instant;
+
+use crate::didmanager::DIDManager;
+use crate::failure::FailureKind;
+use crate::graph::{get_and_create_folder_in, line_plot_latency_vs_document_size};
+use crate::keystorage::KeyStorageConfig;
+use crate::network::IotaTangleNetwork;
+use crate::nodepool::NodeStrategy;
+use crate::statistics::{calculate_stats, mean_of};
+use crate::utils::{durations, save_to_file, Action, Measurement, Outcome, OutputSize, Sample};
+
+/// Length of the value of every custom property added by `DocumentGrowth::Properties`.
+pub const PROPERTY_SIZE: usize = 1024;
+
+/// What a document-size sweep adds to the DID documents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, ValueEnum)]
+pub enum DocumentGrowth {
+    /// Verification methods with a key of the chosen algorithm.
+    Methods,
+    /// LinkedDomains and DIDComm messaging services.
+    Services,
+    /// Custom properties with `PROPERTY_SIZE` random characters each.
+    Properties,
+}
+
+impl DocumentGrowth {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DocumentGrowth::Methods => "Verification methods",
+            DocumentGrowth::Services => "Services",
+            DocumentGrowth::Properties => "Custom properties",
+        }
+    }
+}
+
+/// Random alphanumeric value of a custom property.
+pub fn random_property(length: usize) -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), length)
+}
+
+/// Samples of one document size of a sweep.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SizeStep {
+    pub growth: DocumentGrowth,
+    /// Elements added to every document on top of its first verification method.
+    pub count: usize,
+    pub measurement: Measurement,
+}
+
+impl SizeStep {
+    /// Mean packed document size in bytes of the successful samples of `action`.
+    pub fn mean_document_size(&self, action: Action) -> Option<f64> {
+        let sizes: Vec<f64> = self
+            .output_sizes(action)
+            .map(|size| size.document_size as f64)
+            .collect();
+        (!sizes.is_empty()).then(|| mean_of(&sizes))
+    }
+
+    /// Mean storage deposit in base tokens of the successful samples of `action`.
+    pub fn mean_storage_deposit(&self, action: Action) -> Option<f64> {
+        let deposits: Vec<f64> = self
+            .output_sizes(action)
+            .map(|size| size.storage_deposit as f64)
+            .collect();
+        (!deposits.is_empty()).then(|| mean_of(&deposits))
+    }
+
+    /// The documents of this size do not fit into an Alias Output.
+    pub fn too_large(&self) -> bool {
+        too_large(&self.measurement)
+    }
+
+    fn output_sizes(&self, action: Action) -> impl Iterator<Item = OutputSize> + '_ {
+        self.measurement
+            .get(&action)
+            .into_iter()
+            .flatten()
+            .filter(|sample| sample.outcome == Outcome::Success)
+            .filter_map(|sample| sample.output_size)
+    }
+}
+
+/// Creates and updates `iterations` DIDs for every growth and count, one size after the other,
+/// and reports latency, packed document size and storage deposit per size.
+/// The first backend and algorithm of `key_storage` are used.
+pub async fn run_size_bench(
+    network: &IotaTangleNetwork,
+    node_strategy: NodeStrategy,
+    key_storage: &KeyStorageConfig,
+    growths: &[DocumentGrowth],
+    counts: &[usize],
+    iterations: usize,
+    output_dir: &str,
+) -> anyhow::Result<()> {
+    let (setup, _) = key_storage.setups()[0];
+    let mut steps = vec![];
+
+    for growth in growths {
+        for count in counts {
+            info!("Document size sweep: {} {}", count, growth.name());
+            let mut did_manager =
+                DIDManager::new(network, node_strategy, key_storage, setup.backend)
+                    .await?
+                    .with_key_algorithm(setup.algorithm)
+                    .with_document_growth(*growth, *count);
+
+            let mut measurement = Measurement::new();
+            for index in 0..iterations {
+                for action in [Action::CreateDid, Action::UpdateDid] {
+                    let start_time = Utc::now();
+                    let start = Instant::now();
+                    let result = did_manager.run_action(&action, index).await;
+                    let sample = Sample::new(start_time, start.elapsed(), 0, index)
+                        .with_node(did_manager.current_node())
+                        .with_phases(did_manager.phases())
+                        .with_output_size(did_manager.output_size());
+                    let sample = match result {
+                        Ok(()) => sample,
+                        Err(kind) => sample.failed(kind),
+                    };
+                    measurement.entry(action).or_default().push(sample);
+                }
+                // Every further DID of this size would be rejected the same way
+                if too_large(&measurement) {
+                    warn!(
+                        "{} {} do not fit into an Alias Output, skipping the size",
+                        count,
+                        growth.name()
+                    );
+                    break;
+                }
+            }
+
+            steps.push(SizeStep {
+                growth: *growth,
+                count: *count,
+                measurement,
+            });
+        }
+    }
+
+    print_size_steps(&steps);
+
+    let folder_name = get_and_create_folder_in(output_dir)?;
+    save_to_file(to_string_pretty(&steps)?, &folder_name, "size_sweep")?;
+    if let Err(e) = line_plot_latency_vs_document_size(&folder_name, &steps) {
+        warn!("Failed generate images: {:?}", e);
+    }
+    Ok(())
+}
+
+fn too_large(measurement: &Measurement) -> bool {
+    measurement
+        .values()
+        .flatten()
+        .any(|sample| sample.error_kind == Some(FailureKind::DocumentTooLarge))
+}
+
+pub fn print_size_steps(steps: &[SizeStep]) {
+    println!(
+        "{0: <20} | {1: <6} | {2: <12} | {3: <9} | {4: <12} | {5: <8} | {6: <8} | {7: <8} | {8: <8}",
+        "Growth", "Count", "Action", "Size (B)", "Deposit", "Samples", "Failures", "Mean", "p95"
+    );
+    for step in steps {
+        if step.too_large() {
+            println!(
+                "{0: <20} | {1: <6} | {2: <12} | {3: <9}",
+                step.growth.name(),
+                step.count,
+                "-",
+                "too large"
+            );
+            continue;
+        }
+        for action in [Action::CreateDid, Action::UpdateDid] {
+            let Some(samples) = step.measurement.get(&action) else {
+                continue;
+            };
+            let stats = calculate_stats(&durations(samples));
+            println!(
+                "{0: <20} | {1: <6} | {2: <12} | {3: <9.0} | {4: <12.0} | {5: <8} | {6: <8} | {7: <8.4} | {8: <8.4}",
+                step.growth.name(),
+                step.count,
+                action.name(),
+                step.mean_document_size(action).unwrap_or_default(),
+                step.mean_storage_deposit(action).unwrap_or_default(),
+                samples.len(),
+                samples.len() - stats.count,
+                stats.mean,
+                stats.p95
+            );
+        }
+    }
+}
@@ -0,0 +1,350 @@
+use std::fmt;
+
+use clap::ValueEnum;
+use iota_sdk::client::node_api::error::Error as NodeError;
+use iota_sdk::types::block::output::AliasOutput;
+use iota_sdk::types::block::Error as BlockError;
+use serde::{Deserialize, Serialize};
+use strum::EnumIter;
+use tokio::time::error::Elapsed;
+
+/// Why an action failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter, ValueEnum)]
+pub enum FailureKind {
+    /// The faucet did not fund the address in time.
+    FaucetTimeout,
+    /// The node answered with a 4xx status code.
+    NodeClientError,
+    /// The node answered with a 5xx status code.
+    NodeServerError,
+    /// The node could not be reached.
+    NodeUnreachable,
+    /// No nonce was found or the node rejected the proof of work.
+    PowFailure,
+    /// The transaction consumed an output that was already spent.
+    ConflictingOutput,
+    /// No DID was created at the index used by the action.
+    MissingDid,
+    /// The DID at the index used by the action has no service to change.
+    MissingService,
+    /// The DID at the index used by the action has no credential or presentation to use.
+    MissingCredential,
+    /// A credential or presentation failed validation.
+    InvalidCredential,
+    /// The resolved document does not match the expected one.
+    ResolutionMismatch,
+    /// The block was not included by a milestone or the new state did not resolve in time.
+    ConfirmationTimeout,
+    /// The packed DID document exceeds the state metadata limit of an Alias Output.
+    DocumentTooLarge,
+    /// Any other operation that was given up after a time limit.
+    Timeout,
+    /// Anything else, including error kinds of older raw data files.
+    #[serde(other)]
+    Other,
+}
+
+impl FailureKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            FailureKind::FaucetTimeout => "Faucet timeout",
+            FailureKind::NodeClientError => "Node 4xx",
+            FailureKind::NodeServerError => "Node 5xx",
+            FailureKind::NodeUnreachable => "Node unreachable",
+            FailureKind::PowFailure => "PoW failure",
+            FailureKind::ConflictingOutput => "Conflicting output",
+            FailureKind::MissingDid => "Missing DID",
+            FailureKind::MissingService => "Missing service",
+            FailureKind::MissingCredential => "Missing credential",
+            FailureKind::InvalidCredential => "Invalid credential",
+            FailureKind::ResolutionMismatch => "Resolution mismatch",
+            FailureKind::ConfirmationTimeout => "Confirmation timeout",
+            FailureKind::DocumentTooLarge => "Document too large",
+            FailureKind::Timeout => "Timeout",
+            FailureKind::Other => "Other",
+        }
+    }
+
+    /// Classifies an error by the first cause in its chain that has a known kind.
+    pub fn classify(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(|cause| {
+                if cause.is::<FaucetTimeout>() {
+                    Some(FailureKind::FaucetTimeout)
+                } else if cause.is::<Elapsed>() {
+                    Some(FailureKind::Timeout)
+                } else if cause.is::<MissingDid>() {
+                    Some(FailureKind::MissingDid)
+                } else if cause.is::<MissingService>() {
+                    Some(FailureKind::MissingService)
+                } else if cause.is::<MissingCredential>() {
+                    Some(FailureKind::MissingCredential)
+                } else if cause.is::<InvalidCredential>() {
+                    Some(FailureKind::InvalidCredential)
+                } else if cause.is::<ResolutionMismatch>() {
+                    Some(FailureKind::ResolutionMismatch)
+                } else if cause.is::<ConfirmationTimeout>() {
+                    Some(FailureKind::ConfirmationTimeout)
+                } else if cause.is::<DocumentTooLarge>() {
+                    Some(FailureKind::DocumentTooLarge)
+                } else if cause.is::<ConflictingBlock>() {
+                    Some(FailureKind::ConflictingOutput)
+                } else if let Some(error) = cause.downcast_ref::<iota_sdk::client::Error>() {
+                    Self::classify_client_error(error)
+                } else {
+                    cause
+                        .downcast_ref::<NodeError>()
+                        .map(Self::classify_node_error)
+                }
+            })
+            .unwrap_or(FailureKind::Other)
+    }
+
+    fn classify_client_error(error: &iota_sdk::client::Error) -> Option<Self> {
+        match error {
+            iota_sdk::client::Error::Node(error) => Some(Self::classify_node_error(error)),
+            iota_sdk::client::Error::Block(BlockError::NonceNotFound) => {
+                Some(FailureKind::PowFailure)
+            }
+            iota_sdk::client::Error::TransactionSemantic(_) => Some(FailureKind::ConflictingOutput),
+            _ => None,
+        }
+    }
+
+    fn classify_node_error(error: &NodeError) -> Self {
+        match error {
+            NodeError::ResponseError { code, text, .. } => {
+                let text = text.to_lowercase();
+                if text.contains("conflict") {
+                    FailureKind::ConflictingOutput
+                } else if text.contains("pow") {
+                    FailureKind::PowFailure
+                } else if *code >= 500 {
+                    FailureKind::NodeServerError
+                } else {
+                    FailureKind::NodeClientError
+                }
+            }
+            NodeError::NotFound(_) => FailureKind::NodeClientError,
+            NodeError::Reqwest(_) => FailureKind::NodeUnreachable,
+            NodeError::UnavailablePow => FailureKind::PowFailure,
+            NodeError::NotSupported(_) => FailureKind::Other,
+        }
+    }
+}
+
+impl fmt::Display for FailureKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The faucet did not fund the given address in time.
+#[derive(Debug)]
+pub struct FaucetTimeout(pub String);
+
+impl fmt::Display for FaucetTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the faucet did not fund {} in time", self.0)
+    }
+}
+
+impl std::error::Error for FaucetTimeout {}
+
+/// An action needs a DID that was not created at the given index.
+#[derive(Debug)]
+pub struct MissingDid(pub usize);
+
+impl fmt::Display for MissingDid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No object found at index {}", self.0)
+    }
+}
+
+impl std::error::Error for MissingDid {}
+
+/// An action needs a service in the DID at the given index, but it has none.
+#[derive(Debug)]
+pub struct MissingService(pub usize);
+
+impl fmt::Display for MissingService {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No service found in the DID at index {}", self.0)
+    }
+}
+
+impl std::error::Error for MissingService {}
+
+/// An action needs a credential or presentation of the DID at the given index, but none was created.
+#[derive(Debug)]
+pub struct MissingCredential(pub usize);
+
+impl fmt::Display for MissingCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No credential found for the DID at index {}", self.0)
+    }
+}
+
+impl std::error::Error for MissingCredential {}
+
+/// A credential or presentation was rejected by the validator.
+#[derive(Debug)]
+pub struct InvalidCredential(pub String);
+
+impl fmt::Display for InvalidCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for InvalidCredential {}
+
+/// A resolved document does not match what the benchmark expects.
+#[derive(Debug)]
+pub struct ResolutionMismatch(pub String);
+
+impl fmt::Display for ResolutionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ResolutionMismatch {}
+
+/// A published block or its new state was not confirmed in time.
+#[derive(Debug)]
+pub struct ConfirmationTimeout(pub String);
+
+impl fmt::Display for ConfirmationTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConfirmationTimeout {}
+
+/// A packed DID document of the given size in bytes does not fit into the state metadata of an
+/// Alias Output.
+#[derive(Debug)]
+pub struct DocumentTooLarge(pub usize);
+
+impl fmt::Display for DocumentTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "packed document of {} bytes exceeds the state metadata limit of {} bytes",
+            self.0,
+            AliasOutput::STATE_METADATA_LENGTH_MAX
+        )
+    }
+}
+
+impl std::error::Error for DocumentTooLarge {}
+
+/// A milestone referenced the block but marked its transaction as conflicting.
+#[derive(Debug)]
+pub struct ConflictingBlock(pub String);
+
+impl fmt::Display for ConflictingBlock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "block {} conflicts with the ledger", self.0)
+    }
+}
+
+impl std::error::Error for ConflictingBlock {}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use anyhow::anyhow;
+    use iota_sdk::client::Client;
+    use strum::IntoEnumIterator;
+
+    use super::*;
+
+    fn response_error(code: u16, text: &str) -> anyhow::Error {
+        NodeError::ResponseError {
+            code,
+            text: text.to_string(),
+            url: "http://localhost:14265/api/core/v2/blocks".to_string(),
+        }
+        .into()
+    }
+
+    #[tokio::test]
+    async fn classify_every_kind() {
+        let elapsed = tokio::time::timeout(std::time::Duration::ZERO, std::future::pending::<()>())
+            .await
+            .unwrap_err();
+        // Nothing listens on the discard port
+        let unreachable = Client::get_node_info("http://127.0.0.1:9", None)
+            .await
+            .unwrap_err();
+
+        let cases: Vec<(anyhow::Error, FailureKind)> = vec![
+            (
+                anyhow::Error::new(FaucetTimeout("tst1".to_string())).context("no funds"),
+                FailureKind::FaucetTimeout,
+            ),
+            (anyhow::Error::new(elapsed), FailureKind::Timeout),
+            (
+                response_error(400, "invalid block"),
+                FailureKind::NodeClientError,
+            ),
+            (
+                NodeError::NotFound("output".to_string()).into(),
+                FailureKind::NodeClientError,
+            ),
+            (response_error(503, "busy"), FailureKind::NodeServerError),
+            (unreachable.into(), FailureKind::NodeUnreachable),
+            (
+                iota_sdk::client::Error::Block(BlockError::NonceNotFound).into(),
+                FailureKind::PowFailure,
+            ),
+            (NodeError::UnavailablePow.into(), FailureKind::PowFailure),
+            (
+                response_error(400, "invalid pow score"),
+                FailureKind::PowFailure,
+            ),
+            (
+                response_error(400, "transaction conflicts with the ledger"),
+                FailureKind::ConflictingOutput,
+            ),
+            (
+                ConflictingBlock("0x00".to_string()).into(),
+                FailureKind::ConflictingOutput,
+            ),
+            (MissingDid(1).into(), FailureKind::MissingDid),
+            (MissingService(1).into(), FailureKind::MissingService),
+            (MissingCredential(1).into(), FailureKind::MissingCredential),
+            (
+                InvalidCredential("expired".to_string()).into(),
+                FailureKind::InvalidCredential,
+            ),
+            (
+                ResolutionMismatch("method not found".to_string()).into(),
+                FailureKind::ResolutionMismatch,
+            ),
+            (
+                ConfirmationTimeout("block not included".to_string()).into(),
+                FailureKind::ConfirmationTimeout,
+            ),
+            (DocumentTooLarge(9000).into(), FailureKind::DocumentTooLarge),
+            (anyhow!("something else"), FailureKind::Other),
+        ];
+
+        for (error, expected) in &cases {
+            assert_eq!(FailureKind::classify(error), *expected, "{:?}", error);
+        }
+        let covered: HashSet<FailureKind> = cases.iter().map(|(_, kind)| *kind).collect();
+        assert_eq!(covered, FailureKind::iter().collect());
+    }
+
+    #[test]
+    fn classify_the_first_known_cause() {
+        let error = anyhow::Error::new(MissingDid(3)).context("update failed");
+        assert_eq!(FailureKind::classify(&error), FailureKind::MissingDid);
+    }
+}
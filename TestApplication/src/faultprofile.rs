@@ -0,0 +1,188 @@
+use std::fs::read_to_string;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::{bail, Context};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rand_distr::{Distribution, Exp, LogNormal, Normal};
+use serde::Deserialize;
+
+/// Faults injected by the mock node, loaded from a TOML file:
+///
+/// ```toml
+/// seed = 42
+/// server_error_rate = 0.05
+/// drop_rate = 0.01
+/// faucet_never_funds = false
+///
+/// [latency]
+/// kind = "log-normal"
+/// median_ms = 80
+/// sigma = 0.5
+///
+/// [milestone_delay]
+/// kind = "uniform"
+/// min_ms = 0
+/// max_ms = 10000
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct FaultProfile {
+    /// Seed of the random generator deciding which requests fail. Random if not set.
+    pub seed: Option<u64>,
+    /// Delay added to every response.
+    pub latency: Option<LatencyDistribution>,
+    /// Share of requests answered with 503 Service Unavailable, between 0 and 1.
+    pub server_error_rate: f64,
+    /// Share of connections closed without a response, between 0 and 1.
+    pub drop_rate: f64,
+    /// Delay added to the milestone interval.
+    pub milestone_delay: Option<LatencyDistribution>,
+    /// Accept faucet requests without ever funding the address.
+    pub faucet_never_funds: bool,
+}
+
+/// Distribution of an added delay, in milliseconds.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum LatencyDistribution {
+    Constant {
+        ms: f64,
+    },
+    Uniform {
+        min_ms: f64,
+        max_ms: f64,
+    },
+    Normal {
+        mean_ms: f64,
+        std_dev_ms: f64,
+    },
+    /// Log-normal distribution, as commonly seen for network latency.
+    LogNormal {
+        median_ms: f64,
+        sigma: f64,
+    },
+    Exponential {
+        mean_ms: f64,
+    },
+}
+
+impl FaultProfile {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = read_to_string(path)
+            .with_context(|| format!("failed to read fault profile {}", path.display()))?;
+        let profile: FaultProfile = toml::from_str(&content)
+            .with_context(|| format!("invalid fault profile {}", path.display()))?;
+        profile
+            .validate()
+            .with_context(|| format!("invalid fault profile {}", path.display()))?;
+        Ok(profile)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        for (name, rate) in [
+            ("server_error_rate", self.server_error_rate),
+            ("drop_rate", self.drop_rate),
+        ] {
+            if !(0.0..=1.0).contains(&rate) {
+                bail!("{} must be between 0 and 1, got {}", name, rate);
+            }
+        }
+        for distribution in [&self.latency, &self.milestone_delay].into_iter().flatten() {
+            distribution.validate()?;
+        }
+        Ok(())
+    }
+}
+
+impl LatencyDistribution {
+    fn validate(&self) -> anyhow::Result<()> {
+        let valid = match *self {
+            LatencyDistribution::Constant { ms } => ms >= 0.0,
+            LatencyDistribution::Uniform { min_ms, max_ms } => 0.0 <= min_ms && min_ms <= max_ms,
+            LatencyDistribution::Normal {
+                mean_ms,
+                std_dev_ms,
+            } => mean_ms >= 0.0 && std_dev_ms >= 0.0,
+            LatencyDistribution::LogNormal { median_ms, sigma } => median_ms > 0.0 && sigma >= 0.0,
+            LatencyDistribution::Exponential { mean_ms } => mean_ms > 0.0,
+        };
+        if !valid {
+            bail!("invalid latency distribution {:?}", self);
+        }
+        Ok(())
+    }
+
+    fn sample(&self, rng: &mut StdRng) -> Duration {
+        let ms = match *self {
+            LatencyDistribution::Constant { ms } => ms,
+            LatencyDistribution::Uniform { min_ms, max_ms } => rng.gen_range(min_ms..=max_ms),
+            LatencyDistribution::Normal {
+                mean_ms,
+                std_dev_ms,
+            } => Normal::new(mean_ms, std_dev_ms)
+                .map(|normal| normal.sample(rng))
+                .unwrap_or(mean_ms),
+            LatencyDistribution::LogNormal { median_ms, sigma } => {
+                LogNormal::new(median_ms.ln(), sigma)
+                    .map(|log_normal| log_normal.sample(rng))
+                    .unwrap_or(median_ms)
+            }
+            LatencyDistribution::Exponential { mean_ms } => Exp::new(1.0 / mean_ms)
+                .map(|exp| exp.sample(rng))
+                .unwrap_or(mean_ms),
+        };
+        Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+    }
+}
+
+/// Draws the faults of a [`FaultProfile`] from a shared random generator.
+pub struct FaultInjector {
+    profile: FaultProfile,
+    rng: Mutex<StdRng>,
+}
+
+impl FaultInjector {
+    pub fn new(profile: FaultProfile) -> Self {
+        let rng = match profile.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self {
+            profile,
+            rng: Mutex::new(rng),
+        }
+    }
+
+    pub fn profile(&self) -> &FaultProfile {
+        &self.profile
+    }
+
+    /// Delay to add to the next response.
+    pub fn latency(&self) -> Option<Duration> {
+        self.profile
+            .latency
+            .map(|latency| latency.sample(&mut self.rng.lock().unwrap()))
+    }
+
+    /// Delay to add to the next milestone interval.
+    pub fn milestone_delay(&self) -> Option<Duration> {
+        self.profile
+            .milestone_delay
+            .map(|delay| delay.sample(&mut self.rng.lock().unwrap()))
+    }
+
+    pub fn server_error(&self) -> bool {
+        self.chance(self.profile.server_error_rate)
+    }
+
+    pub fn drop_connection(&self) -> bool {
+        self.chance(self.profile.drop_rate)
+    }
+
+    fn chance(&self, rate: f64) -> bool {
+        rate > 0.0 && self.rng.lock().unwrap().gen_bool(rate)
+    }
+}
@@ -2,8 +2,9 @@ use anyhow::Ok;
 use chrono::{format::format, Utc};
 use log::info;
 use rayon::vec;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::path::PathBuf;
 use strum::IntoEnumIterator;
 
 use plotly::{
@@ -11,16 +12,28 @@ use plotly::{
     color::Rgb,
     common::{Anchor, Font, Line, Marker, Mode, Orientation, Title},
     layout::{
-        Annotation, Axis, GridPattern, Layout, LayoutGrid, Legend, Margin, RowOrder, TraceOrder,
+        Annotation, Axis, BarMode, BoxMode, GridPattern, Layout, LayoutGrid, Legend, Margin,
+        RowOrder, TraceOrder,
     },
-    BoxPlot, Configuration, ImageFormat, Plot, Scatter,
+    Bar, BoxPlot, Configuration, ImageFormat, Plot, Scatter,
 };
 
-use crate::utils::{Action, IotaTangleNetwork, Measurement};
+use crate::buildandpostblocktest::{
+    load_block_test_results, BuildBlockAndPublishStatResult, PowSettings,
+};
+use crate::documentsize::{DocumentGrowth, SizeStep};
+use crate::network::Networks;
+use crate::statistics::mean_of;
+use crate::utils::{durations, Action, Measurement, Phase};
 
 pub fn get_and_create_folder() -> anyhow::Result<String> {
+    get_and_create_folder_in("test")
+}
+
+pub fn get_and_create_folder_in(output_dir: &str) -> anyhow::Result<String> {
     let folder_name = format!(
-        "test/{}",
+        "{}/{}",
+        output_dir,
         Utc::now().format("%Y-%m-%d_%H-%M-%S").to_string()
     );
     fs::create_dir_all(folder_name.clone())?;
@@ -29,10 +42,15 @@ pub fn get_and_create_folder() -> anyhow::Result<String> {
 
 pub fn draw_all_measurements(
     folder_name: &str,
-    measurements: &HashMap<IotaTangleNetwork, Measurement>,
+    measurements: &HashMap<String, Measurement>,
+    known_networks: &Networks,
 ) -> anyhow::Result<()> {
-    for (network, durations) in measurements {
-        draw_action_measurements(network.name(), durations, folder_name);
+    for (network_id, measurement) in measurements {
+        draw_action_measurements(
+            &known_networks.name_of(network_id),
+            measurement,
+            folder_name,
+        );
     }
 
     Ok(())
@@ -42,8 +60,8 @@ pub fn draw_action_measurements(title: &str, measurements: &Measurement, folder_
     let mut values: Vec<(String, Vec<f64>)> = Vec::new();
 
     for action in Action::iter() {
-        if let Some(durations) = measurements.get(&action) {
-            values.push((action.name().to_string(), durations.clone()));
+        if let Some(samples) = measurements.get(&action) {
+            values.push((action.name().to_string(), durations(samples)));
         }
     }
 
@@ -99,11 +117,7 @@ fn draw_box_plot(folder_name: &str, title: &str, values: &Vec<(String, Vec<f64>)
         plot.add_trace(trace);
     }
 
-    let plot_name = format!("{}/{}_boxplot", folder_name, title)
-        .replace(" ", "_")
-        .replace("(", "_")
-        .replace(")", "_")
-        .replace(":", "_");
+    let plot_name = plot_file_name(folder_name, title, "boxplot");
     let plot_name_png = format!("{}.png", plot_name);
     let plot_name_svg = format!("{}.svg", plot_name);
     info!("{}", plot_name);
@@ -112,97 +126,350 @@ fn draw_box_plot(folder_name: &str, title: &str, values: &Vec<(String, Vec<f64>)
     plot.write_image(plot_name_svg, ImageFormat::SVG, 1100, 380, 1.0);
 }
 
-struct RemoteLocalValues {
+/// Draws one box per run and action, grouped by action.
+pub fn draw_grouped_box_plot(folder_name: &str, title: &str, runs: &[(String, &Measurement)]) {
+    let mut plot = Plot::new();
+    let layout = Layout::new()
+        .title(Title::with_text(title).font(Font::new().size(18)))
+        .y_axis(
+            Axis::new()
+                .title(Title::with_text("Time (seconds)").font(Font::new().size(16)))
+                .auto_range(true)
+                .auto_margin(true)
+                .show_grid(true)
+                .show_line(true)
+                .zero_line(false)
+                .grid_color(Rgb::new(150, 150, 150))
+                .grid_width(1)
+                .line_color(Rgb::new(0, 0, 0))
+                .line_width(2)
+                .tick_font(Font::new().size(15).color("#898989")),
+        )
+        .x_axis(
+            Axis::new()
+                .auto_range(true)
+                .auto_margin(true)
+                .show_grid(false)
+                .show_line(true)
+                .zero_line(false)
+                .line_color(Rgb::new(0, 0, 0))
+                .line_width(2)
+                .tick_font(Font::new().size(15).color("#898989")),
+        )
+        .box_mode(BoxMode::Group)
+        .margin(Margin::new().left(10).right(10).bottom(20).top(50))
+        .paper_background_color(Rgb::new(250, 250, 250))
+        .plot_background_color(Rgb::new(250, 250, 250))
+        .show_legend(true);
+    plot.set_layout(layout);
+
+    for (label, measurement) in runs {
+        let mut x = Vec::new();
+        let mut y = Vec::new();
+        for action in Action::iter() {
+            if let Some(samples) = measurement.get(&action) {
+                for duration in durations(samples) {
+                    x.push(action.name().to_string());
+                    y.push(duration);
+                }
+            }
+        }
+
+        let trace = BoxPlot::new_xy(x, y)
+            .name(label)
+            .box_points(BoxPoints::Outliers)
+            .whisker_width(0.2)
+            .marker(Marker::new().size(4))
+            .line(Line::new().width(2.0));
+        plot.add_trace(trace);
+    }
+
+    let plot_name = plot_file_name(folder_name, title, "comparison_boxplot");
+    let plot_name_png = format!("{}.png", plot_name);
+    let plot_name_svg = format!("{}.svg", plot_name);
+    info!("{}", plot_name);
+
+    plot.write_image(plot_name_png, ImageFormat::PNG, 1100, 380, 1.0);
+    plot.write_image(plot_name_svg, ImageFormat::SVG, 1100, 380, 1.0);
+}
+
+/// Draws the mean duration of each phase of building and posting a block, stacked per node configuration.
+pub fn draw_stacked_phase_plot(
+    folder_name: &str,
+    title: &str,
+    results: &HashMap<Action, BuildBlockAndPublishStatResult>,
+) {
+    let mut plot = Plot::new();
+    let layout = Layout::new()
+        .title(Title::with_text(title).font(Font::new().size(18)))
+        .y_axis(
+            Axis::new()
+                .title(Title::with_text("Mean time (seconds)").font(Font::new().size(16)))
+                .auto_range(true)
+                .auto_margin(true)
+                .show_grid(true)
+                .show_line(true)
+                .zero_line(false)
+                .grid_color(Rgb::new(150, 150, 150))
+                .grid_width(1)
+                .line_color(Rgb::new(0, 0, 0))
+                .line_width(2)
+                .tick_font(Font::new().size(15).color("#898989")),
+        )
+        .x_axis(
+            Axis::new()
+                .auto_range(true)
+                .auto_margin(true)
+                .show_grid(false)
+                .show_line(true)
+                .zero_line(false)
+                .line_color(Rgb::new(0, 0, 0))
+                .line_width(2)
+                .tick_font(Font::new().size(15).color("#898989")),
+        )
+        .bar_mode(BarMode::Stack)
+        .margin(Margin::new().left(10).right(10).bottom(20).top(50))
+        .paper_background_color(Rgb::new(250, 250, 250))
+        .plot_background_color(Rgb::new(250, 250, 250))
+        .show_legend(true);
+    plot.set_layout(layout);
+
+    for phase in Phase::iter() {
+        let mut x = Vec::new();
+        let mut y = Vec::new();
+        for action in Action::iter() {
+            if let Some(stats) = results.get(&action).and_then(|r| r.phases.get(&phase)) {
+                x.push(action.name().to_string());
+                y.push(stats.mean);
+            }
+        }
+        if x.is_empty() {
+            continue;
+        }
+        plot.add_trace(Bar::new(x, y).name(phase.name()));
+    }
+
+    let plot_name = plot_file_name(folder_name, title, "phases");
+    let plot_name_png = format!("{}.png", plot_name);
+    let plot_name_svg = format!("{}.svg", plot_name);
+    info!("{}", plot_name);
+
+    plot.write_image(plot_name_png, ImageFormat::PNG, 1100, 380, 1.0);
+    plot.write_image(plot_name_svg, ImageFormat::SVG, 1100, 380, 1.0);
+}
+
+fn plot_file_name(folder_name: &str, title: &str, suffix: &str) -> String {
+    format!("{}/{}_{}", folder_name, title, suffix)
+        .replace(" ", "_")
+        .replace("(", "_")
+        .replace(")", "_")
+        .replace(":", "_")
+}
+
+/// One line of a subplot.
+struct Series {
+    pub name: String,
+    pub x_values: Vec<f64>,
+    pub y_values: Vec<f64>,
+}
+
+struct Subplot {
     pub title: String,
-    pub remote: Vec<f64>,
-    pub local: Vec<f64>,
+    pub series: Vec<Series>,
 }
 
 struct PlotData {
-    pub x_values: Vec<f64>,
     pub x_axis_title: String,
-    pub y_values: Vec<RemoteLocalValues>,
     pub y_axis_title: String,
+    pub subplots: Vec<Subplot>,
 }
 
-pub fn line_plot_decline_bps_vs_node_count() {
+/// Plotly supports up to eight pairs of axes, more subplots are split over several images.
+const MAX_SUBPLOTS: usize = 8;
+const SUBPLOT_COLUMNS: usize = 3;
+const SERIES_COLORS: [&str; 4] = ["blue", "green", "red", "orange"];
+
+/// BPS per node count, one subplot per min PoW score, from the `results` files of block test runs.
+pub fn line_plot_decline_bps_vs_node_count(
+    folders: &[PathBuf],
+    fallback: PowSettings,
+) -> anyhow::Result<()> {
+    let results = load_all_block_test_results(folders, fallback)?;
     let data = PlotData {
         x_axis_title: "Node Count".to_string(),
-        x_values: vec![2.0, 3.0, 4.0],
         y_axis_title: "BPS".to_string(),
-        y_values: vec![
-            RemoteLocalValues {
-                title: "Min PoW Score 0".to_string(),
-                remote: vec![920.332, 871.325, 832.758],
-                local: vec![484.565, 472.579, 449.282],
-            },
-            RemoteLocalValues {
-                title: "Min PoW Score 750".to_string(),
-                remote: vec![74.266, 73.859, 72.693],
-                local: vec![44.945, 44.222, 43.557],
-            },
-            RemoteLocalValues {
-                title: "Min PoW Score 1500".to_string(),
-                remote: vec![26.366, 25.315, 24.762],
-                local: vec![15.814, 15.980, 15.884],
-            },
-        ],
+        subplots: bps_subplots(
+            &results,
+            |result| result.min_pow_score,
+            |result| result.node_count,
+            |min_pow_score| format!("Min PoW Score {}", min_pow_score),
+        ),
     };
 
-    create_plot(data, "bps_decline_for_different_node_count");
+    create_plot(data, "bps_decline_for_different_node_count")
 }
 
-pub fn line_plot_decline_bps_vs_min_pow_score() {
+/// BPS per min PoW score, one subplot per node count, from the `results` files of block test runs.
+pub fn line_plot_decline_bps_vs_min_pow_score(
+    folders: &[PathBuf],
+    fallback: PowSettings,
+) -> anyhow::Result<()> {
+    let results = load_all_block_test_results(folders, fallback)?;
     let data = PlotData {
         x_axis_title: "MinPoWScore".to_string(),
-        x_values: vec![0.0, 750.0, 1500.0],
         y_axis_title: "BPS".to_string(),
-        y_values: vec![
-            RemoteLocalValues {
-                title: "Nodes = 2".to_string(),
-                remote: vec![920.332, 74.266, 26.366],
-                local: vec![484.565, 44.945, 15.814],
-            },
-            RemoteLocalValues {
-                title: "Nodes = 3".to_string(),
-                remote: vec![871.325, 73.859, 25.315],
-                local: vec![472.579, 44.222, 15.980],
-            },
-            RemoteLocalValues {
-                title: "Nodes = 4".to_string(),
-                remote: vec![832.758, 72.693, 24.762],
-                local: vec![449.282, 43.557, 15.884],
-            },
-        ],
+        subplots: bps_subplots(
+            &results,
+            |result| result.node_count,
+            |result| result.min_pow_score,
+            |node_count| format!("Nodes = {}", node_count),
+        ),
+    };
+
+    create_plot(data, "bps_decline_for_different_pow_scores")
+}
+
+/// Mean latency of creating and updating a DID per mean packed document size, one subplot per
+/// kind of document growth, from the steps of a document-size sweep.
+pub fn line_plot_latency_vs_document_size(
+    folder_name: &str,
+    steps: &[SizeStep],
+) -> anyhow::Result<()> {
+    let mut grouped: BTreeMap<DocumentGrowth, Vec<&SizeStep>> = BTreeMap::new();
+    for step in steps {
+        grouped.entry(step.growth).or_default().push(step);
+    }
+
+    let subplots = grouped
+        .into_iter()
+        .map(|(growth, steps)| Subplot {
+            title: growth.name().to_string(),
+            series: [Action::CreateDid, Action::UpdateDid]
+                .into_iter()
+                .map(|action| {
+                    let (x_values, y_values) = steps
+                        .iter()
+                        .filter_map(|step| {
+                            let size = step.mean_document_size(action)?;
+                            let latencies = durations(step.measurement.get(&action)?);
+                            (!latencies.is_empty()).then(|| (size, mean_of(&latencies)))
+                        })
+                        .unzip();
+                    Series {
+                        name: action.name().to_string(),
+                        x_values,
+                        y_values,
+                    }
+                })
+                .collect(),
+        })
+        .collect();
+
+    let data = PlotData {
+        x_axis_title: "Packed document size (bytes)".to_string(),
+        y_axis_title: "Mean latency (seconds)".to_string(),
+        subplots,
     };
+    create_plot(data, &format!("{}/latency_vs_document_size", folder_name))
+}
 
-    create_plot(data, "bps_decline_for_different_pow_scores");
+fn load_all_block_test_results(
+    folders: &[PathBuf],
+    fallback: PowSettings,
+) -> anyhow::Result<Vec<BuildBlockAndPublishStatResult>> {
+    let mut results = Vec::new();
+    for folder in folders {
+        results.extend(load_block_test_results(folder, fallback)?.into_values());
+    }
+    Ok(results)
 }
 
-fn create_plot(data: PlotData, file_name: &str) {
+/// Groups the results into subplots and a remote and local PoW series per subplot.
+/// Runs with the same configuration are averaged.
+fn bps_subplots(
+    results: &[BuildBlockAndPublishStatResult],
+    subplot_key: impl Fn(&BuildBlockAndPublishStatResult) -> usize,
+    x_key: impl Fn(&BuildBlockAndPublishStatResult) -> usize,
+    subplot_title: impl Fn(usize) -> String,
+) -> Vec<Subplot> {
+    let mut grouped: BTreeMap<usize, BTreeMap<bool, BTreeMap<usize, Vec<f64>>>> = BTreeMap::new();
+    for result in results {
+        grouped
+            .entry(subplot_key(result))
+            .or_default()
+            .entry(result.local_pow)
+            .or_default()
+            .entry(x_key(result))
+            .or_default()
+            .push(result.bps);
+    }
+
+    grouped
+        .into_iter()
+        .map(|(key, series)| Subplot {
+            title: subplot_title(key),
+            series: series
+                .into_iter()
+                .map(|(local_pow, points)| Series {
+                    name: if local_pow { "Local" } else { "Remote" }.to_string(),
+                    x_values: points.keys().map(|x| *x as f64).collect(),
+                    y_values: points
+                        .values()
+                        .map(|bps| bps.iter().sum::<f64>() / bps.len() as f64)
+                        .collect(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Draws the subplots into `file_name.png`, or into `file_name_1.png`, `file_name_2.png`, ...
+/// if there are more than [`MAX_SUBPLOTS`].
+fn create_plot(data: PlotData, file_name: &str) -> anyhow::Result<()> {
+    if data.subplots.is_empty() {
+        anyhow::bail!("no results to plot");
+    }
+    if data.subplots.len() <= MAX_SUBPLOTS {
+        draw_subplots(&data, &data.subplots, file_name);
+        return Ok(());
+    }
+    for (index, subplots) in data.subplots.chunks(MAX_SUBPLOTS).enumerate() {
+        draw_subplots(&data, subplots, &format!("{}_{}", file_name, index + 1));
+    }
+    Ok(())
+}
+
+/// Draws up to [`MAX_SUBPLOTS`] subplots with the axis titles of `data` into one image.
+fn draw_subplots(data: &PlotData, subplots: &[Subplot], file_name: &str) {
+    let subplot_count = subplots.len();
+    let columns = subplot_count.min(SUBPLOT_COLUMNS);
+    let rows = subplot_count.div_ceil(columns);
+
     let mut plot = Plot::new();
+    let mut series_names: Vec<&str> = Vec::new();
+
+    for (index, subplot) in subplots.iter().enumerate() {
+        for series in &subplot.series {
+            // Show every series once in the legend
+            let show_legend = !series_names.contains(&series.name.as_str());
+            if show_legend {
+                series_names.push(&series.name);
+            }
+            let color_index = series_names
+                .iter()
+                .position(|name| *name == series.name)
+                .unwrap_or(0);
 
-    for (index, trace_data) in data.y_values.iter().enumerate() {
-        let show_legend = index < 1;
-        let trace_remote = Scatter::new(data.x_values.clone(), trace_data.remote.clone())
-            .name("Remote")
-            .mode(Mode::LinesMarkers)
-            .marker(Marker::new().color("blue"))
-            .x_axis(format!("x{}", (index + 1)))
-            .y_axis(format!("y{}", (index + 1)))
-            .show_legend(show_legend);
-
-        let trace_local = Scatter::new(data.x_values.clone(), trace_data.local.clone())
-            .name("Local")
-            .mode(Mode::LinesMarkers)
-            .marker(Marker::new().color("green"))
-            .x_axis(format!("x{}", (index + 1)))
-            .y_axis(format!("y{}", (index + 1)))
-            .show_legend(show_legend);
-
-        // Add traces to the plot
-        plot.add_trace(trace_remote);
-        plot.add_trace(trace_local);
+            let trace = Scatter::new(series.x_values.clone(), series.y_values.clone())
+                .name(&series.name)
+                .legend_group(&series.name)
+                .mode(Mode::LinesMarkers)
+                .marker(Marker::new().color(SERIES_COLORS[color_index % SERIES_COLORS.len()]))
+                .x_axis(format!("x{}", (index + 1)))
+                .y_axis(format!("y{}", (index + 1)))
+                .show_legend(show_legend);
+            plot.add_trace(trace);
+        }
     }
 
     // Create common axis
@@ -219,7 +486,6 @@ fn create_plot(data: PlotData, file_name: &str) {
         .tick_font(Font::new().size(15).color("#898989"));
 
     let x_axis_template = Axis::new()
-        .tick_values(data.x_values.clone())
         .auto_range(true)
         .auto_margin(true)
         .show_grid(false)
@@ -232,9 +498,9 @@ fn create_plot(data: PlotData, file_name: &str) {
         .tick_font(Font::new().size(15).color("#898989"));
 
     let mut annotaions: Vec<Annotation> = Vec::new();
-    for (index, rem_loc_values) in data.y_values.iter().enumerate() {
+    for (index, subplot) in subplots.iter().enumerate() {
         let annotaion = plotly::layout::Annotation::new()
-            .text(rem_loc_values.title.clone())
+            .text(subplot.title.clone())
             .font(Font::new().size(15))
             .x(0.5)
             .y(1.15)
@@ -244,20 +510,14 @@ fn create_plot(data: PlotData, file_name: &str) {
         annotaions.push(annotaion);
     }
 
-    // Define layout with grid (3 plots in a row) and titles for each plot
-    let layout = Layout::new()
+    // Define layout with a grid of up to three plots per row and titles for each plot
+    let mut layout = Layout::new()
         .grid(
             LayoutGrid::new()
-                .rows(1)
-                .columns(3)
+                .rows(rows)
+                .columns(columns)
                 .pattern(plotly::layout::GridPattern::Independent),
         )
-        .x_axis(x_axis_template.clone().title(data.x_axis_title.clone()))
-        .x_axis2(x_axis_template.clone().title(data.x_axis_title.clone()))
-        .x_axis3(x_axis_template.clone().title(data.x_axis_title.clone()))
-        .y_axis(y_axis_template.clone().title(data.y_axis_title.clone()))
-        .y_axis2(y_axis_template.clone())
-        .y_axis3(y_axis_template.clone())
         .annotations(annotaions)
         .show_legend(true)
         .legend(
@@ -268,11 +528,32 @@ fn create_plot(data: PlotData, file_name: &str) {
                 .font(Font::new().size(15)),
         )
         .width(1200)
-        .height(320)
+        .height(320 * rows)
         .margin(Margin::new().left(10).right(10).bottom(35).top(35))
         .paper_background_color(Rgb::new(250, 250, 250))
         .plot_background_color(Rgb::new(250, 250, 250));
-    // .title("BPS Decline for Different PoW Scores");
+
+    for (index, subplot) in subplots.iter().enumerate() {
+        let mut tick_values: Vec<f64> = subplot
+            .series
+            .iter()
+            .flat_map(|series| series.x_values.iter().copied())
+            .collect();
+        tick_values.sort_by(f64::total_cmp);
+        tick_values.dedup();
+
+        let x_axis = x_axis_template
+            .clone()
+            .tick_values(tick_values)
+            .title(data.x_axis_title.clone());
+        // Only the first plot of a row has a y axis title
+        let y_axis = if index % columns == 0 {
+            y_axis_template.clone().title(data.y_axis_title.clone())
+        } else {
+            y_axis_template.clone()
+        };
+        layout = with_subplot_axes(layout, index, x_axis, y_axis);
+    }
 
     // Set layout and show plot
     plot.set_layout(layout);
@@ -282,6 +563,20 @@ fn create_plot(data: PlotData, file_name: &str) {
     let plot_name_png = format!("{}.png", file_name);
     // let plot_name_svg = format!("{}.svg", file_name);
 
-    plot.write_image(plot_name_png, ImageFormat::PNG, 1200, 250, 1.0);
-    // plot.write_image(plot_name_svg, ImageFormat::SVG, 1200, 250, 1.0);
+    plot.write_image(plot_name_png, ImageFormat::PNG, 1200, 250 * rows, 1.0);
+    // plot.write_image(plot_name_svg, ImageFormat::SVG, 1200, 250 * rows, 1.0);
+}
+
+fn with_subplot_axes(layout: Layout, index: usize, x_axis: Axis, y_axis: Axis) -> Layout {
+    match index {
+        0 => layout.x_axis(x_axis).y_axis(y_axis),
+        1 => layout.x_axis2(x_axis).y_axis2(y_axis),
+        2 => layout.x_axis3(x_axis).y_axis3(y_axis),
+        3 => layout.x_axis4(x_axis).y_axis4(y_axis),
+        4 => layout.x_axis5(x_axis).y_axis5(y_axis),
+        5 => layout.x_axis6(x_axis).y_axis6(y_axis),
+        6 => layout.x_axis7(x_axis).y_axis7(y_axis),
+        7 => layout.x_axis8(x_axis).y_axis8(y_axis),
+        _ => layout,
+    }
 }
@@ -0,0 +1,357 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use clap::ValueEnum;
+use identity_iota::{
+    credential::{Credential, Jwt, JwtPresentationOptions, Presentation},
+    did::DIDUrl,
+    iota::IotaDocument,
+    storage::{JwkDocumentExt, JwkMemStore, JwsSignatureOptions, KeyIdMemstore, KeyType, Storage},
+    verification::{jws::JwsAlgorithm, MethodScope},
+};
+use identity_stronghold::StrongholdStorage;
+use iota_sdk::client::{
+    secret::{stronghold::StrongholdSecretManager, SecretManager},
+    Password,
+};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::random_mnemonic;
+
+/// Environment variable, also read from `.env`, with the password of the stronghold snapshots.
+pub const STRONGHOLD_PASSWORD_VAR: &str = "STRONGHOLD_PASSWORD";
+
+/// Where the keys of the wallet and of the DID verification methods are kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum StorageBackend {
+    /// Encrypted stronghold snapshot on disk.
+    #[default]
+    Stronghold,
+    /// In memory only, for pure performance runs. The keys are lost when the task ends.
+    Memory,
+}
+
+impl StorageBackend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            StorageBackend::Stronghold => "stronghold",
+            StorageBackend::Memory => "memory",
+        }
+    }
+}
+
+/// Key type and signature algorithm of the verification methods.
+/// `JwkMemStore` and `StrongholdStorage` only generate Ed25519 keys for `generate_method` so far;
+/// further algorithms are added here once a storage supports them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum KeyAlgorithm {
+    /// Ed25519 keys signing with EdDSA.
+    #[default]
+    Ed25519,
+}
+
+impl KeyAlgorithm {
+    pub fn name(&self) -> &'static str {
+        match self {
+            KeyAlgorithm::Ed25519 => "ed25519",
+        }
+    }
+
+    pub fn key_type(&self) -> KeyType {
+        match self {
+            KeyAlgorithm::Ed25519 => JwkMemStore::ED25519_KEY_TYPE,
+        }
+    }
+
+    pub fn jws_algorithm(&self) -> JwsAlgorithm {
+        match self {
+            KeyAlgorithm::Ed25519 => JwsAlgorithm::EdDSA,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct KeyStorageConfig {
+    /// Backends to run the benchmark with, one after the other.
+    pub backends: Vec<StorageBackend>,
+    /// Algorithms of the verification methods to run the benchmark with, one after the other.
+    pub algorithms: Vec<KeyAlgorithm>,
+    /// Password of the stronghold snapshots, see [`KeyStorageConfig::load_password`].
+    pub password: Option<Password>,
+    /// Keep the random stronghold snapshots in `test_strongholds` after the run.
+    pub keep_strongholds: bool,
+}
+
+impl fmt::Debug for KeyStorageConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyStorageConfig")
+            .field("backends", &self.backends)
+            .field("algorithms", &self.algorithms)
+            .field("password", &self.password.as_ref().map(|_| "***"))
+            .field("keep_strongholds", &self.keep_strongholds)
+            .finish()
+    }
+}
+
+impl Default for KeyStorageConfig {
+    fn default() -> Self {
+        Self {
+            backends: vec![StorageBackend::default()],
+            algorithms: vec![KeyAlgorithm::default()],
+            password: None,
+            keep_strongholds: false,
+        }
+    }
+}
+
+/// Backend and algorithm of one run of the benchmark.
+#[derive(Debug, Clone, Copy)]
+pub struct KeySetup {
+    pub backend: StorageBackend,
+    pub algorithm: KeyAlgorithm,
+}
+
+impl KeyStorageConfig {
+    /// Every combination of the backends and algorithms, with a label naming the values that differ
+    /// between the combinations, e.g. `memory`. The label is `None` if there is only one combination.
+    pub fn setups(&self) -> Vec<(KeySetup, Option<String>)> {
+        let mut setups = vec![];
+        for backend in &self.backends {
+            for algorithm in &self.algorithms {
+                let mut label = vec![];
+                if self.backends.len() > 1 {
+                    label.push(backend.name());
+                }
+                if self.algorithms.len() > 1 {
+                    label.push(algorithm.name());
+                }
+                let setup = KeySetup {
+                    backend: *backend,
+                    algorithm: *algorithm,
+                };
+                setups.push((setup, (!label.is_empty()).then(|| label.join(", "))));
+            }
+        }
+        setups
+    }
+
+    /// Reads the stronghold password from `STRONGHOLD_PASSWORD`, or asks for it if it is not set.
+    /// Does nothing if the stronghold backend is not used.
+    pub fn load_password(&mut self) -> anyhow::Result<()> {
+        if !self.backends.contains(&StorageBackend::Stronghold) || self.password.is_some() {
+            return Ok(());
+        }
+
+        let password = match std::env::var(STRONGHOLD_PASSWORD_VAR) {
+            Ok(password) => password,
+            Err(_) => rpassword::prompt_password("Stronghold password: ")
+                .context("failed to read the stronghold password")?,
+        };
+        if password.is_empty() {
+            anyhow::bail!("the stronghold password must not be empty");
+        }
+        self.password = Some(Password::from(password));
+        Ok(())
+    }
+}
+
+/// Keys of one `DIDManager`.
+pub enum KeyStorage {
+    Stronghold {
+        stronghold_storage: StrongholdStorage,
+        storage: Storage<StrongholdStorage, StrongholdStorage>,
+        path: PathBuf,
+        /// Delete the snapshot when the storage is dropped.
+        temporary: bool,
+    },
+    Memory {
+        secret_manager: Box<SecretManager>,
+        storage: Storage<JwkMemStore, KeyIdMemstore>,
+    },
+}
+
+impl KeyStorage {
+    /// Opens the stronghold snapshot at `path`, or creates it. Temporary snapshots are deleted
+    /// when the storage is dropped, unless `config` keeps them.
+    pub fn stronghold(
+        config: &KeyStorageConfig,
+        path: PathBuf,
+        temporary: bool,
+    ) -> anyhow::Result<Self> {
+        let password = config
+            .password
+            .clone()
+            .context("no stronghold password loaded")?;
+        let stronghold = StrongholdSecretManager::builder()
+            .password(password)
+            .build(path.clone())?;
+
+        // Create a `StrongholdStorage`.
+        // `StrongholdStorage` creates internally a `SecretManager` that can be
+        // referenced to avoid creating multiple instances around the same stronghold snapshot.
+        let stronghold_storage = StrongholdStorage::new(stronghold);
+        let storage = Storage::new(stronghold_storage.clone(), stronghold_storage.clone());
+
+        Ok(KeyStorage::Stronghold {
+            stronghold_storage,
+            storage,
+            path,
+            temporary: temporary && !config.keep_strongholds,
+        })
+    }
+
+    pub fn memory() -> anyhow::Result<Self> {
+        Ok(KeyStorage::Memory {
+            secret_manager: Box::new(SecretManager::try_from_mnemonic(random_mnemonic()?)?),
+            storage: Storage::new(JwkMemStore::new(), KeyIdMemstore::new()),
+        })
+    }
+
+    /// Secret manager of the wallet that signs the transactions.
+    pub fn secret_manager(&self) -> &SecretManager {
+        match self {
+            KeyStorage::Stronghold {
+                stronghold_storage, ..
+            } => stronghold_storage.as_secret_manager(),
+            KeyStorage::Memory { secret_manager, .. } => secret_manager,
+        }
+    }
+
+    /// Snapshot path of the stronghold backend.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            KeyStorage::Stronghold { path, .. } => Some(path),
+            KeyStorage::Memory { .. } => None,
+        }
+    }
+
+    /// Generates a verification method with a key of `algorithm` in `document` and returns its fragment.
+    pub async fn generate_method(
+        &self,
+        document: &mut IotaDocument,
+        algorithm: KeyAlgorithm,
+    ) -> anyhow::Result<String> {
+        let fragment = match self {
+            KeyStorage::Stronghold { storage, .. } => {
+                document
+                    .generate_method(
+                        storage,
+                        algorithm.key_type(),
+                        algorithm.jws_algorithm(),
+                        None,
+                        MethodScope::VerificationMethod,
+                    )
+                    .await?
+            }
+            KeyStorage::Memory { storage, .. } => {
+                document
+                    .generate_method(
+                        storage,
+                        algorithm.key_type(),
+                        algorithm.jws_algorithm(),
+                        None,
+                        MethodScope::VerificationMethod,
+                    )
+                    .await?
+            }
+        };
+        Ok(fragment)
+    }
+
+    /// Removes the verification method `id` from `document` and deletes its key.
+    pub async fn purge_method(
+        &self,
+        document: &mut IotaDocument,
+        id: &DIDUrl,
+    ) -> anyhow::Result<()> {
+        match self {
+            KeyStorage::Stronghold { storage, .. } => document.purge_method(storage, id).await?,
+            KeyStorage::Memory { storage, .. } => document.purge_method(storage, id).await?,
+        }
+        Ok(())
+    }
+
+    /// Signs `credential` as a JWT with the key of the method `fragment` of the issuer `document`.
+    pub async fn create_credential_jwt(
+        &self,
+        document: &IotaDocument,
+        fragment: &str,
+        credential: &Credential,
+    ) -> anyhow::Result<Jwt> {
+        let options = JwsSignatureOptions::default();
+        let jwt = match self {
+            KeyStorage::Stronghold { storage, .. } => {
+                document
+                    .create_credential_jwt(credential, storage, fragment, &options, None)
+                    .await?
+            }
+            KeyStorage::Memory { storage, .. } => {
+                document
+                    .create_credential_jwt(credential, storage, fragment, &options, None)
+                    .await?
+            }
+        };
+        Ok(jwt)
+    }
+
+    /// Signs `presentation` as a JWT with the key of the method `fragment` of the holder `document`,
+    /// answering the `challenge` of the verifier.
+    pub async fn create_presentation_jwt(
+        &self,
+        document: &IotaDocument,
+        fragment: &str,
+        presentation: &Presentation<Jwt>,
+        challenge: &str,
+    ) -> anyhow::Result<Jwt> {
+        let signature_options = JwsSignatureOptions::default().nonce(challenge);
+        let presentation_options = JwtPresentationOptions::default();
+        let jwt = match self {
+            KeyStorage::Stronghold { storage, .. } => {
+                document
+                    .create_presentation_jwt(
+                        presentation,
+                        storage,
+                        fragment,
+                        &signature_options,
+                        &presentation_options,
+                    )
+                    .await?
+            }
+            KeyStorage::Memory { storage, .. } => {
+                document
+                    .create_presentation_jwt(
+                        presentation,
+                        storage,
+                        fragment,
+                        &signature_options,
+                        &presentation_options,
+                    )
+                    .await?
+            }
+        };
+        Ok(jwt)
+    }
+}
+
+impl Drop for KeyStorage {
+    fn drop(&mut self) {
+        if let KeyStorage::Stronghold {
+            path,
+            temporary: true,
+            ..
+        } = self
+        {
+            match std::fs::remove_file(&*path) {
+                Ok(()) => info!("Removed stronghold snapshot {}", path.display()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => (),
+                Err(e) => warn!(
+                    "Failed to remove stronghold snapshot {}: {}",
+                    path.display(),
+                    e
+                ),
+            }
+        }
+    }
+}
@@ -1,17 +1,36 @@
-use std::process::Command;
+use std::path::Path;
+use std::time::Duration;
 
-use buildandpostblocktest::run_for_all_nodes_configurations_block_test;
+use buildandpostblocktest::{
+    run_for_all_nodes_configurations_block_test, run_for_both_pow_modes_block_test, PowSettings,
+};
+use clap::Parser;
+use cli::{Cli, Command, PlotKind};
+use documentsize::run_size_bench;
+use faultprofile::FaultProfile;
 use graph::{line_plot_decline_bps_vs_min_pow_score, line_plot_decline_bps_vs_node_count};
-use log::{info, warn};
+use mocknode::{run_mock_node, spawn_mock_node, MockNodeConfig};
+use network::Networks;
 use resolvedidtest::resolve_did_test;
-use testutils::{read_and_print_raw_data, run_test};
-use tokio::time::{sleep, Duration};
-use utils::{wait_until_enter_pressed, IotaTangleNetwork};
+use scenario::{Scenario, ScenarioAction};
+use testutils::{compare_raw_data, compare_runs, read_and_print_raw_data, run_scenario};
 
 mod buildandpostblocktest;
+mod cli;
+mod consistency;
 mod didmanager;
+mod documentsize;
+mod failure;
+mod faultprofile;
 mod graph;
+mod keystorage;
+mod mocknode;
+mod network;
+mod nodepool;
 mod resolvedidtest;
+mod retry;
+mod scenario;
+mod statistics;
 mod testutils;
 mod utils;
 
@@ -21,101 +40,220 @@ pub struct Params {
     pub iterations: usize,
 }
 
+const DEFAULT_NETWORKS_FILE: &str = "networks.toml";
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     dotenvy::dotenv().map_err(|e| anyhow::anyhow!("Failed to load .env file: {}", e))?;
     env_logger::init();
 
-    /////////////////////// Test DID functions /////////////////////////////
-    // let num_threads = std::cmp::min(num_cpus::get(), 1);
-    // let iterations = 1;
-    let params = vec![
-        // Params {
-        //     num_threads: 1,
-        //     iterations: 500,
-        // },
-        Params {
-            num_threads: 5,
-            iterations: 10,
-        },
-    ];
-
-    for param in params {
-        let networks = vec![
-            IotaTangleNetwork::Localhost,
-            // IotaTangleNetwork::IotaTestnet,
-            // IotaTangleNetwork::ShimmerTestnet,
-        ];
-
-        if let Err(e) = run_test(&networks, param.num_threads, param.iterations).await {
-            log::error!("Error occurred in test_localhost: {:?}", e);
-            return Err(e);
-        }
+    let cli = Cli::parse();
+    let known_networks = load_networks(cli.networks_file.as_deref())?;
+
+    if cli.with_mock_node {
+        spawn_mock_node(MockNodeConfig {
+            faults: load_fault_profile(cli.mock_faults.as_deref())?,
+            ..MockNodeConfig::default()
+        })
+        .await?;
     }
 
-    ///////////////////// Create one DID and resolve /////////////////////////////
-    // resolve_did_test().await;
-
-    /////////////////////// Build and post blocks /////////////////////////////
-    // let number_of_tasks = 2;
-    // let number_of_iterations = 10_000;
-    // let local_pow = true;
-    // let min_pow_score = 0; // Just for the title of the graph
-    // run_for_all_nodes_configurations_block_test(
-    //     number_of_tasks,
-    //     number_of_iterations,
-    //     local_pow,
-    //     min_pow_score,
-    // )
-    // .await;
-
-    // for index in 0..2 {
-    //     let number_of_tasks = 2;
-    //     let number_of_iterations = 10_000;
-    //     let local_pow = if index == 0 { false } else { true };
-    //     let min_pow_score = 0; // Just for the title of the graph
-    //     run_for_all_nodes_configurations_block_test(
-    //         number_of_tasks,
-    //         number_of_iterations,
-    //         local_pow,
-    //         min_pow_score,
-    //     )
-    //     .await;
-
-    //     let result = Command::new("docker")
-    //         .arg("compose")
-    //         .arg("start")
-    //         .arg("hornet-3")
-    //         .arg("hornet-4")
-    //         .current_dir("../PrivateTangle")
-    //         .status();
-
-    //     match result {
-    //         Ok(output) => {
-    //             // let stdout = String::from_utf8_lossy(&output.stdout);
-    //             info!("Command Output:\n{}", output);
-    //         }
-    //         Err(e) => {
-    //             warn!("Failed to execute command: {}", e);
-    //         }
-    //     }
-    //     sleep(Duration::from_secs(60)).await;
-    // }
-
-    /////////////////////// Generate line graphs /////////////////////////////
-    // line_plot_decline_bps_vs_node_count();
-    // line_plot_decline_bps_vs_min_pow_score();
-
-    /////////////////////// Print raw_data /////////////////////////////
-    // let file_names = vec![
-    //     "./temp/iota_testnet",
-    //     "./temp/localhost_1_task_500",
-    //     "./temp/localhost_5_tasks_100",
-    // ];
-    // for file_name in file_names {
-    //     read_and_print_raw_data(file_name);
-    // }
+    match cli.command {
+        /////////////////////// Test DID functions /////////////////////////////
+        Command::DidBench {
+            params,
+            networks,
+            actions,
+            output_dir,
+            node_strategy,
+            retry,
+            confirmation,
+            track_cost,
+            consistency,
+            state_dir,
+            key_storage,
+        } => {
+            let mut scenario = Scenario {
+                networks: known_networks.get_all(&networks)?,
+                params,
+                actions: actions.into_iter().map(ScenarioAction::new).collect(),
+                output_dir,
+                node_strategy,
+                retry_policy: retry.policy()?,
+                confirmation,
+                track_cost,
+                consistency: consistency.config(),
+                state_dir,
+                key_storage: key_storage.config(),
+                source: None,
+            };
+            scenario.validate()?;
+            scenario.key_storage.load_password()?;
+
+            if let Err(e) = run_scenario(&scenario, &known_networks).await {
+                log::error!("Error occurred in run_scenario: {:?}", e);
+                return Err(e);
+            }
+        }
+
+        Command::Run { scenario } => {
+            let mut scenario = Scenario::load(&scenario, &known_networks)?;
+            scenario.key_storage.load_password()?;
+
+            if let Err(e) = run_scenario(&scenario, &known_networks).await {
+                log::error!("Error occurred in run_scenario: {:?}", e);
+                return Err(e);
+            }
+        }
+
+        ///////////////////// Create one DID and resolve /////////////////////////////
+        Command::ResolveBench {
+            network,
+            num_threads,
+            iterations,
+            node_strategy,
+            key_storage,
+        } => {
+            let network = known_networks.get(&network)?;
+            let mut key_storage = key_storage.config();
+            key_storage.load_password()?;
+            resolve_did_test(
+                &network,
+                node_strategy,
+                &key_storage,
+                num_threads,
+                iterations,
+            )
+            .await;
+        }
+
+        ///////////////////// Grow DID documents step by step /////////////////////////////
+        Command::SizeBench {
+            network,
+            growths,
+            counts,
+            iterations,
+            output_dir,
+            node_strategy,
+            key_storage,
+        } => {
+            let network = known_networks.get(&network)?;
+            let mut key_storage = key_storage.config();
+            key_storage.load_password()?;
+            run_size_bench(
+                &network,
+                node_strategy,
+                &key_storage,
+                &growths,
+                &counts,
+                iterations,
+                &output_dir,
+            )
+            .await?;
+        }
+
+        /////////////////////// Build and post blocks /////////////////////////////
+        Command::BlockBench {
+            networks,
+            node_strategy,
+            num_tasks,
+            iterations,
+            local_pow,
+            min_pow_score,
+            both_pow_modes,
+        } => {
+            let networks = known_networks.get_all(&networks)?;
+            if both_pow_modes {
+                run_for_both_pow_modes_block_test(
+                    &networks,
+                    node_strategy,
+                    num_tasks,
+                    iterations,
+                    min_pow_score,
+                )
+                .await?;
+            } else {
+                run_for_all_nodes_configurations_block_test(
+                    &networks,
+                    node_strategy,
+                    num_tasks,
+                    iterations,
+                    local_pow,
+                    min_pow_score,
+                )
+                .await?;
+            }
+        }
+
+        /////////////////////// Generate line graphs /////////////////////////////
+        Command::Plot {
+            kind,
+            folders,
+            local_pow,
+            min_pow_score,
+        } => {
+            let fallback = PowSettings {
+                local_pow,
+                min_pow_score,
+            };
+            match kind {
+                PlotKind::NodeCount => line_plot_decline_bps_vs_node_count(&folders, fallback)?,
+                PlotKind::PowScore => line_plot_decline_bps_vs_min_pow_score(&folders, fallback)?,
+            }
+        }
+
+        /////////////////////// Compare raw_data /////////////////////////////
+        Command::Compare {
+            baseline,
+            candidate,
+            alpha,
+        } => compare_raw_data(&baseline, &candidate, alpha, &known_networks)?,
+
+        /////////////////////// Mock node /////////////////////////////
+        Command::MockNode {
+            port,
+            min_pow_score,
+            milestone_interval,
+            faults,
+        } => {
+            run_mock_node(MockNodeConfig {
+                port,
+                min_pow_score,
+                milestone_interval: Duration::from_millis(milestone_interval),
+                faults: load_fault_profile(faults.as_deref())?,
+                ..MockNodeConfig::default()
+            })
+            .await?
+        }
+
+        /////////////////////// Print raw_data /////////////////////////////
+        Command::Report { files, output_dir } => {
+            for file_name in &files {
+                read_and_print_raw_data(file_name, &known_networks);
+            }
+            if files.len() > 1 {
+                compare_runs(&files, &output_dir, &known_networks)?;
+            }
+        }
+    }
 
     log::info!("Application finished successfully.");
     Ok(())
 }
+
+fn load_networks(networks_file: Option<&Path>) -> anyhow::Result<Networks> {
+    match networks_file {
+        Some(path) => Networks::load(path),
+        None if Path::new(DEFAULT_NETWORKS_FILE).exists() => {
+            Networks::load(Path::new(DEFAULT_NETWORKS_FILE))
+        }
+        None => Ok(Networks::builtin()),
+    }
+}
+
+fn load_fault_profile(path: Option<&Path>) -> anyhow::Result<FaultProfile> {
+    match path {
+        Some(path) => FaultProfile::load(path),
+        None => Ok(FaultProfile::default()),
+    }
+}
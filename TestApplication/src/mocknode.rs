@@ -0,0 +1,784 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use axum::body::Bytes;
+use axum::extract::{Path, Query, Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use hyper_util::rt::TokioIo;
+use iota_sdk::packable::PackableExt;
+use iota_sdk::types::api::core::response::{
+    BaseTokenResponse, BlockMetadataResponse, ConfirmedMilestoneResponse, InfoResponse,
+    LatestMilestoneResponse, LedgerInclusionState, MetricsResponse, OutputWithMetadataResponse,
+    StatusResponse, SubmitBlockResponse, TipsResponse,
+};
+use iota_sdk::types::api::plugins::indexer::OutputIdsResponse;
+use iota_sdk::types::block::address::{Address, Bech32Address, Hrp};
+use iota_sdk::types::block::input::Input;
+use iota_sdk::types::block::output::dto::OutputDto;
+use iota_sdk::types::block::output::unlock_condition::AddressUnlockCondition;
+use iota_sdk::types::block::output::{
+    AliasId, BasicOutputBuilder, Output, OutputId, OutputMetadata, RentStructure,
+};
+use iota_sdk::types::block::payload::transaction::{TransactionEssence, TransactionId};
+use iota_sdk::types::block::payload::Payload;
+use iota_sdk::types::block::protocol::ProtocolParameters;
+use iota_sdk::types::block::{Block, BlockDto, BlockId};
+use iota_sdk::types::TryFromDto;
+use log::{debug, info};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+use tower::Service;
+
+use crate::faultprofile::{FaultInjector, FaultProfile};
+
+const PROTOCOL_VERSION: u8 = 2;
+const BELOW_MAX_DEPTH: u8 = 15;
+const TOKEN_SUPPLY: u64 = 1_813_620_509_061_365;
+/// Content type of blocks posted in their packed binary form.
+const PACKED_BLOCK_TYPE: &str = "application/vnd.iota.serializer-v1";
+
+/// Settings of a [`MockNode`].
+#[derive(Debug, Clone)]
+pub struct MockNodeConfig {
+    pub port: u16,
+    pub network_name: String,
+    pub bech32_hrp: String,
+    pub min_pow_score: u32,
+    /// Time between two milestones. Blocks are referenced by the next milestone after they were posted.
+    pub milestone_interval: Duration,
+    /// Amount sent by the faucet for every request.
+    pub faucet_amount: u64,
+    pub faults: FaultProfile,
+}
+
+impl Default for MockNodeConfig {
+    fn default() -> Self {
+        Self {
+            port: 14365,
+            network_name: "mock".to_string(),
+            bech32_hrp: "tst".to_string(),
+            min_pow_score: 0,
+            milestone_interval: Duration::from_secs(1),
+            faucet_amount: 1_000_000_000_000,
+            faults: FaultProfile::default(),
+        }
+    }
+}
+
+struct LedgerOutput {
+    output: Output,
+    block_id: BlockId,
+    milestone_index_booked: u32,
+    milestone_timestamp_booked: u32,
+    /// Transaction and milestone that spent the output.
+    spent: Option<(TransactionId, u32, u32)>,
+}
+
+struct StoredBlock {
+    block: Block,
+    has_transaction: bool,
+    referenced_by_milestone_index: Option<u32>,
+}
+
+/// In-memory ledger of the mock node.
+struct Ledger {
+    outputs: HashMap<OutputId, LedgerOutput>,
+    blocks: HashMap<BlockId, StoredBlock>,
+    /// Posted blocks not referenced by a milestone yet.
+    pending: Vec<BlockId>,
+    tip: BlockId,
+    milestone_index: u32,
+    milestone_timestamp: u32,
+}
+
+/// A node implementing the subset of the Hornet core and indexer REST API used by the
+/// iota-sdk `Client`, plus a faucet. Transactions are applied to the in-memory ledger when posted,
+/// without checking signatures or proof of work.
+pub struct MockNode {
+    config: MockNodeConfig,
+    protocol_parameters: ProtocolParameters,
+    ledger: Mutex<Ledger>,
+    faults: FaultInjector,
+}
+
+impl MockNode {
+    pub fn new(config: MockNodeConfig) -> anyhow::Result<Self> {
+        let protocol_parameters = ProtocolParameters::new(
+            PROTOCOL_VERSION,
+            config.network_name.clone(),
+            Hrp::from_str(&config.bech32_hrp)?,
+            config.min_pow_score,
+            BELOW_MAX_DEPTH,
+            RentStructure::default(),
+            TOKEN_SUPPLY,
+        )?;
+
+        Ok(Self {
+            faults: FaultInjector::new(config.faults.clone()),
+            config,
+            protocol_parameters,
+            ledger: Mutex::new(Ledger {
+                outputs: HashMap::new(),
+                blocks: HashMap::new(),
+                pending: Vec::new(),
+                tip: BlockId::new(rand::random()),
+                milestone_index: 1,
+                milestone_timestamp: unix_timestamp(),
+            }),
+        })
+    }
+
+    /// Issues a milestone referencing every pending block.
+    fn issue_milestone(&self) {
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger.milestone_index += 1;
+        ledger.milestone_timestamp = unix_timestamp();
+
+        let index = ledger.milestone_index;
+        for block_id in std::mem::take(&mut ledger.pending) {
+            if let Some(block) = ledger.blocks.get_mut(&block_id) {
+                block.referenced_by_milestone_index = Some(index);
+            }
+        }
+    }
+
+    fn info(&self) -> InfoResponse {
+        let ledger = self.ledger.lock().unwrap();
+
+        InfoResponse {
+            name: "HORNET".to_string(),
+            version: "mock".to_string(),
+            status: StatusResponse {
+                is_healthy: true,
+                latest_milestone: LatestMilestoneResponse {
+                    index: ledger.milestone_index,
+                    timestamp: Some(ledger.milestone_timestamp),
+                    milestone_id: None,
+                },
+                confirmed_milestone: ConfirmedMilestoneResponse {
+                    index: ledger.milestone_index,
+                    timestamp: Some(ledger.milestone_timestamp),
+                    milestone_id: None,
+                },
+                pruning_index: 0,
+            },
+            supported_protocol_versions: vec![PROTOCOL_VERSION],
+            protocol: self.protocol_parameters.clone(),
+            pending_protocol_parameters: vec![],
+            base_token: BaseTokenResponse {
+                name: "Mock".to_string(),
+                ticker_symbol: "MCK".to_string(),
+                unit: "MCK".to_string(),
+                subunit: Some("glow".to_string()),
+                decimals: 6,
+                use_metric_prefix: false,
+            },
+            metrics: MetricsResponse {
+                blocks_per_second: 0.0,
+                referenced_blocks_per_second: 0.0,
+                referenced_rate: 0.0,
+            },
+            features: vec!["pow".to_string()],
+        }
+    }
+
+    /// Validates a block and applies its transaction, if any, to the ledger.
+    fn submit_block(&self, block: Block) -> Result<BlockId, ApiError> {
+        let block_id = block.id();
+        let mut ledger = self.ledger.lock().unwrap();
+
+        let has_transaction = match block.payload() {
+            Some(Payload::Transaction(transaction)) => {
+                let TransactionEssence::Regular(essence) = transaction.essence();
+                let transaction_id = transaction.id();
+
+                for input in essence.inputs() {
+                    let Input::Utxo(input) = input else {
+                        return Err(ApiError::bad_request("treasury inputs are not supported"));
+                    };
+                    match ledger.outputs.get(input.output_id()) {
+                        Some(output) if output.spent.is_none() => {}
+                        Some(_) => {
+                            return Err(ApiError::bad_request(format!(
+                                "transaction {} conflicts with the ledger: input {} already spent",
+                                transaction_id,
+                                input.output_id()
+                            )))
+                        }
+                        None => {
+                            return Err(ApiError::bad_request(format!(
+                                "transaction {} conflicts with the ledger: input {} not found",
+                                transaction_id,
+                                input.output_id()
+                            )))
+                        }
+                    }
+                }
+
+                let (index, timestamp) = (ledger.milestone_index, ledger.milestone_timestamp);
+                for input in essence.inputs() {
+                    if let Input::Utxo(input) = input {
+                        if let Some(output) = ledger.outputs.get_mut(input.output_id()) {
+                            output.spent = Some((transaction_id, index, timestamp));
+                        }
+                    }
+                }
+                for (output_index, output) in essence.outputs().iter().enumerate() {
+                    let output_id = OutputId::new(transaction_id, output_index as u16)
+                        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+                    ledger.outputs.insert(
+                        output_id,
+                        LedgerOutput {
+                            output: output.clone(),
+                            block_id,
+                            milestone_index_booked: index,
+                            milestone_timestamp_booked: timestamp,
+                            spent: None,
+                        },
+                    );
+                }
+                true
+            }
+            _ => false,
+        };
+
+        ledger.blocks.insert(
+            block_id,
+            StoredBlock {
+                block,
+                has_transaction,
+                referenced_by_milestone_index: None,
+            },
+        );
+        ledger.pending.push(block_id);
+        ledger.tip = block_id;
+        debug!("Mock node received block {}", block_id);
+
+        Ok(block_id)
+    }
+
+    /// Creates a basic output owned by `address`.
+    fn fund(&self, address: Address) -> anyhow::Result<()> {
+        if self.faults.profile().faucet_never_funds {
+            debug!("Mock faucet ignores request for {}", address);
+            return Ok(());
+        }
+
+        let output = BasicOutputBuilder::new_with_amount(self.config.faucet_amount)
+            .add_unlock_condition(AddressUnlockCondition::new(address))
+            .finish_output(self.protocol_parameters.token_supply())?;
+        let output_id = OutputId::new(TransactionId::new(rand::random()), 0)?;
+
+        let mut ledger = self.ledger.lock().unwrap();
+        let (index, timestamp) = (ledger.milestone_index, ledger.milestone_timestamp);
+        ledger.outputs.insert(
+            output_id,
+            LedgerOutput {
+                output,
+                block_id: BlockId::new(rand::random()),
+                milestone_index_booked: index,
+                milestone_timestamp_booked: timestamp,
+                spent: None,
+            },
+        );
+        Ok(())
+    }
+
+    /// Ids of the unspent outputs matching `filter`.
+    fn output_ids(&self, filter: impl Fn(&OutputId, &Output) -> bool) -> OutputIdsResponse {
+        let ledger = self.ledger.lock().unwrap();
+        let mut items: Vec<OutputId> = ledger
+            .outputs
+            .iter()
+            .filter(|(output_id, output)| {
+                output.spent.is_none() && filter(output_id, &output.output)
+            })
+            .map(|(output_id, _)| *output_id)
+            .collect();
+        items.sort();
+
+        OutputIdsResponse {
+            ledger_index: ledger.milestone_index,
+            cursor: None,
+            items,
+        }
+    }
+
+    fn output_metadata(&self, ledger: &Ledger, output_id: &OutputId) -> Option<OutputMetadata> {
+        ledger.outputs.get(output_id).map(|output| {
+            OutputMetadata::new(
+                output.block_id,
+                *output_id,
+                output.spent.is_some(),
+                output.spent.map(|(_, index, _)| index),
+                output.spent.map(|(_, _, timestamp)| timestamp),
+                output.spent.map(|(transaction_id, _, _)| transaction_id),
+                output.milestone_index_booked,
+                output.milestone_timestamp_booked,
+                ledger.milestone_index,
+            )
+        })
+    }
+}
+
+/// Error in the format of the Hornet REST API.
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            message: message.into(),
+        }
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self {
+            status: StatusCode::NOT_FOUND,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = json!({
+            "error": {
+                "code": self.status.as_u16().to_string(),
+                "message": self.message,
+            }
+        });
+        (self.status, Json(body)).into_response()
+    }
+}
+
+type SharedNode = Arc<MockNode>;
+
+/// Starts a mock node and its milestone ticker in the background.
+/// Returns the address the node listens on.
+pub async fn spawn_mock_node(
+    config: MockNodeConfig,
+) -> anyhow::Result<(SocketAddr, JoinHandle<()>)> {
+    let listener = TcpListener::bind(("127.0.0.1", config.port)).await?;
+    let address = listener.local_addr()?;
+    let milestone_interval = config.milestone_interval;
+    let node: SharedNode = Arc::new(MockNode::new(config)?);
+
+    let ticker_node = node.clone();
+    tokio::spawn(async move {
+        loop {
+            let delay = ticker_node.faults.milestone_delay().unwrap_or_default();
+            tokio::time::sleep(milestone_interval + delay).await;
+            ticker_node.issue_milestone();
+        }
+    });
+
+    let app = router(node.clone());
+    let handle = if node.faults.profile().drop_rate > 0.0 {
+        tokio::spawn(serve_dropping_connections(listener, app, node))
+    } else {
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                log::error!("Mock node stopped: {:?}", e);
+            }
+        })
+    };
+
+    info!("Mock node listening on http://{}", address);
+    Ok((address, handle))
+}
+
+/// Serves every connection on its own, without keep-alive, so a share of the requests
+/// can be answered by closing the connection.
+async fn serve_dropping_connections(listener: TcpListener, app: Router, node: SharedNode) {
+    loop {
+        let (stream, _) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                log::error!("Mock node stopped: {:?}", e);
+                return;
+            }
+        };
+        if node.faults.drop_connection() {
+            debug!("Mock node drops a connection");
+            drop(stream);
+            continue;
+        }
+
+        let app = app.clone();
+        tokio::spawn(async move {
+            let service = hyper::service::service_fn(move |request| app.clone().call(request));
+            if let Err(e) = hyper::server::conn::http1::Builder::new()
+                .keep_alive(false)
+                .serve_connection(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Mock node connection failed: {:?}", e);
+            }
+        });
+    }
+}
+
+/// Adds the latency and server errors of the fault profile to every request.
+async fn inject_faults(State(node): State<SharedNode>, request: Request, next: Next) -> Response {
+    if let Some(latency) = node.faults.latency() {
+        tokio::time::sleep(latency).await;
+    }
+    if node.faults.server_error() {
+        return ApiError {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            message: "injected fault".to_string(),
+        }
+        .into_response();
+    }
+    next.run(request).await
+}
+
+/// Runs a mock node until the process is stopped.
+pub async fn run_mock_node(config: MockNodeConfig) -> anyhow::Result<()> {
+    let (_, handle) = spawn_mock_node(config).await?;
+    handle.await?;
+    Ok(())
+}
+
+fn router(node: SharedNode) -> Router {
+    Router::new()
+        .route("/health", get(|| async { StatusCode::OK }))
+        .route("/api/core/v2/info", get(get_info))
+        .route("/api/core/v2/tips", get(get_tips))
+        .route("/api/core/v2/blocks", post(post_block))
+        .route("/api/core/v2/blocks/:block_id", get(get_block))
+        .route(
+            "/api/core/v2/blocks/:block_id/metadata",
+            get(get_block_metadata),
+        )
+        .route("/api/core/v2/outputs/:output_id", get(get_output))
+        .route(
+            "/api/core/v2/outputs/:output_id/metadata",
+            get(get_output_metadata),
+        )
+        .route("/api/indexer/v1/outputs/basic", get(get_basic_output_ids))
+        .route("/api/indexer/v1/outputs/alias", get(get_alias_output_ids))
+        .route(
+            "/api/indexer/v1/outputs/alias/:alias_id",
+            get(get_alias_output_id),
+        )
+        .route("/api/indexer/v1/outputs/nft", get(get_empty_output_ids))
+        .route("/api/indexer/v1/outputs/foundry", get(get_empty_output_ids))
+        .route("/api/enqueue", post(enqueue_faucet_request))
+        .layer(middleware::from_fn_with_state(node.clone(), inject_faults))
+        .with_state(node)
+}
+
+async fn get_info(State(node): State<SharedNode>) -> Json<InfoResponse> {
+    Json(node.info())
+}
+
+async fn get_tips(State(node): State<SharedNode>) -> Json<TipsResponse> {
+    let ledger = node.ledger.lock().unwrap();
+    Json(TipsResponse {
+        tips: vec![ledger.tip],
+    })
+}
+
+/// Accepts a block as JSON or, like `Client::post_block_raw` sends it, as packed bytes.
+async fn post_block(
+    State(node): State<SharedNode>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(StatusCode, Json<SubmitBlockResponse>), ApiError> {
+    let packed = headers
+        .get(CONTENT_TYPE)
+        .is_some_and(|content_type| content_type.as_bytes() == PACKED_BLOCK_TYPE.as_bytes());
+    let block = if packed {
+        Block::unpack_verified(body.as_ref(), &node.protocol_parameters)
+            .map_err(|e| ApiError::bad_request(format!("invalid block: {}", e)))?
+    } else {
+        let dto: BlockDto = serde_json::from_slice(&body)
+            .map_err(|e| ApiError::bad_request(format!("invalid block: {}", e)))?;
+        Block::try_from_dto_with_params(dto, &node.protocol_parameters)
+            .map_err(|e| ApiError::bad_request(format!("invalid block: {}", e)))?
+    };
+    let block_id = node.submit_block(block)?;
+
+    Ok((StatusCode::CREATED, Json(SubmitBlockResponse { block_id })))
+}
+
+async fn get_block(
+    State(node): State<SharedNode>,
+    Path(block_id): Path<String>,
+) -> Result<Json<BlockDto>, ApiError> {
+    let block_id = parse_id::<BlockId>(&block_id)?;
+    let ledger = node.ledger.lock().unwrap();
+    let block = ledger
+        .blocks
+        .get(&block_id)
+        .ok_or_else(|| ApiError::not_found(format!("block {} not found", block_id)))?;
+
+    Ok(Json(BlockDto::from(&block.block)))
+}
+
+async fn get_block_metadata(
+    State(node): State<SharedNode>,
+    Path(block_id): Path<String>,
+) -> Result<Json<BlockMetadataResponse>, ApiError> {
+    let block_id = parse_id::<BlockId>(&block_id)?;
+    let ledger = node.ledger.lock().unwrap();
+    let block = ledger
+        .blocks
+        .get(&block_id)
+        .ok_or_else(|| ApiError::not_found(format!("block {} not found", block_id)))?;
+
+    let ledger_inclusion_state = block.referenced_by_milestone_index.map(|_| {
+        if block.has_transaction {
+            LedgerInclusionState::Included
+        } else {
+            LedgerInclusionState::NoTransaction
+        }
+    });
+
+    Ok(Json(BlockMetadataResponse {
+        block_id,
+        parents: block.block.parents().to_vec(),
+        is_solid: true,
+        referenced_by_milestone_index: block.referenced_by_milestone_index,
+        milestone_index: None,
+        ledger_inclusion_state,
+        conflict_reason: None,
+        white_flag_index: None,
+        should_promote: Some(false),
+        should_reattach: Some(false),
+    }))
+}
+
+async fn get_output(
+    State(node): State<SharedNode>,
+    Path(output_id): Path<String>,
+) -> Result<Json<OutputWithMetadataResponse>, ApiError> {
+    let output_id = parse_id::<OutputId>(&output_id)?;
+    let ledger = node.ledger.lock().unwrap();
+    let metadata = node
+        .output_metadata(&ledger, &output_id)
+        .ok_or_else(|| ApiError::not_found(format!("output {} not found", output_id)))?;
+
+    Ok(Json(OutputWithMetadataResponse {
+        metadata,
+        output: OutputDto::from(&ledger.outputs[&output_id].output),
+    }))
+}
+
+async fn get_output_metadata(
+    State(node): State<SharedNode>,
+    Path(output_id): Path<String>,
+) -> Result<Json<OutputMetadata>, ApiError> {
+    let output_id = parse_id::<OutputId>(&output_id)?;
+    let ledger = node.ledger.lock().unwrap();
+    let metadata = node
+        .output_metadata(&ledger, &output_id)
+        .ok_or_else(|| ApiError::not_found(format!("output {} not found", output_id)))?;
+
+    Ok(Json(metadata))
+}
+
+/// Query parameters of the indexer routes. Only the address filters are supported.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexerQuery {
+    address: Option<String>,
+    state_controller: Option<String>,
+    governor: Option<String>,
+}
+
+async fn get_basic_output_ids(
+    State(node): State<SharedNode>,
+    Query(query): Query<IndexerQuery>,
+) -> Result<Json<OutputIdsResponse>, ApiError> {
+    let address = parse_address(query.address.as_deref())?;
+
+    Ok(Json(node.output_ids(|_, output| match output {
+        Output::Basic(basic) => address.is_none_or(|address| basic.address() == &address),
+        _ => false,
+    })))
+}
+
+async fn get_alias_output_ids(
+    State(node): State<SharedNode>,
+    Query(query): Query<IndexerQuery>,
+) -> Result<Json<OutputIdsResponse>, ApiError> {
+    let state_controller = parse_address(query.state_controller.as_deref())?;
+    let governor = parse_address(query.governor.as_deref())?;
+
+    Ok(Json(node.output_ids(|_, output| match output {
+        Output::Alias(alias) => {
+            state_controller.is_none_or(|address| alias.state_controller_address() == &address)
+                && governor.is_none_or(|address| alias.governor_address() == &address)
+        }
+        _ => false,
+    })))
+}
+
+async fn get_alias_output_id(
+    State(node): State<SharedNode>,
+    Path(alias_id): Path<String>,
+) -> Result<Json<OutputIdsResponse>, ApiError> {
+    let alias_id = parse_id::<AliasId>(&alias_id)?;
+    let response = node.output_ids(|output_id, output| match output {
+        Output::Alias(alias) => alias.alias_id_non_null(output_id) == alias_id,
+        _ => false,
+    });
+    if response.items.is_empty() {
+        return Err(ApiError::not_found(format!("alias {} not found", alias_id)));
+    }
+
+    Ok(Json(response))
+}
+
+async fn get_empty_output_ids(State(node): State<SharedNode>) -> Json<OutputIdsResponse> {
+    Json(node.output_ids(|_, _| false))
+}
+
+#[derive(Debug, Deserialize)]
+struct FaucetRequest {
+    address: String,
+}
+
+async fn enqueue_faucet_request(
+    State(node): State<SharedNode>,
+    Json(request): Json<FaucetRequest>,
+) -> Result<(StatusCode, Json<serde_json::Value>), ApiError> {
+    let address = parse_address(Some(&request.address))?
+        .ok_or_else(|| ApiError::bad_request("missing address"))?;
+    node.fund(address)
+        .map_err(|e| ApiError::bad_request(e.to_string()))?;
+
+    Ok((
+        StatusCode::ACCEPTED,
+        Json(json!({ "address": request.address, "waitingRequests": 0 })),
+    ))
+}
+
+fn parse_id<T: FromStr>(value: &str) -> Result<T, ApiError> {
+    T::from_str(value).map_err(|_| ApiError::bad_request(format!("invalid id `{}`", value)))
+}
+
+fn parse_address(value: Option<&str>) -> Result<Option<Address>, ApiError> {
+    value
+        .map(|value| {
+            Bech32Address::try_from_str(value)
+                .map(|address| *address.inner())
+                .map_err(|_| ApiError::bad_request(format!("invalid address `{}`", value)))
+        })
+        .transpose()
+}
+
+fn unix_timestamp() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as u32)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use identity_iota::iota::IotaDocument;
+    use identity_iota::prelude::Resolver;
+    use iota_sdk::client::Client;
+
+    use super::*;
+    use crate::didmanager::DIDManager;
+    use crate::keystorage::{KeyStorageConfig, StorageBackend};
+    use crate::network::IotaTangleNetwork;
+    use crate::nodepool::NodeStrategy;
+    use crate::utils::Action;
+
+    #[tokio::test]
+    async fn did_lifecycle_on_mock_node() {
+        let (address, _) = spawn_mock_node(MockNodeConfig {
+            port: 0,
+            milestone_interval: Duration::from_millis(100),
+            ..MockNodeConfig::default()
+        })
+        .await
+        .unwrap();
+        let network = IotaTangleNetwork {
+            id: "Mock".to_string(),
+            name: "Mock node".to_string(),
+            api_endpoints: vec![format!("http://{}", address)],
+            faucet_endpoint: format!("http://{}/api/enqueue", address),
+            auth_token: None,
+            network_name: Some("mock".to_string()),
+            bech32_hrp: Some("tst".to_string()),
+        };
+        let key_storage = KeyStorageConfig {
+            backends: vec![StorageBackend::Memory],
+            ..KeyStorageConfig::default()
+        };
+        let mut did_manager = DIDManager::new(
+            &network,
+            NodeStrategy::default(),
+            &key_storage,
+            StorageBackend::Memory,
+        )
+        .await
+        .unwrap();
+
+        let client = Client::builder()
+            .with_node(&network.api_endpoints[0])
+            .unwrap()
+            .finish()
+            .await
+            .unwrap();
+        let mut resolver = Resolver::<IotaDocument>::new();
+        resolver.attach_iota_handler(client);
+
+        did_manager.run_action(&Action::CreateDid, 0).await.unwrap();
+        let did = did_manager.did(0).unwrap().clone();
+        let document = resolver.resolve(&did).await.unwrap();
+        assert_eq!(document.id(), &did);
+        assert_eq!(document.methods(None).len(), 1);
+        let first_method = document.methods(None)[0].id().clone();
+
+        // The update replaces the verification method
+        did_manager.run_action(&Action::UpdateDid, 0).await.unwrap();
+        let document = resolver.resolve(&did).await.unwrap();
+        assert_eq!(document.methods(None).len(), 1);
+        assert_ne!(document.methods(None)[0].id(), &first_method);
+
+        did_manager
+            .run_action(&Action::ResolveDid, 0)
+            .await
+            .unwrap();
+
+        did_manager
+            .run_action(&Action::DeactivateDid, 0)
+            .await
+            .unwrap();
+        let document = resolver.resolve(&did).await.unwrap();
+        assert_eq!(document.metadata.deactivated, Some(true));
+
+        did_manager
+            .run_action(&Action::ReactivateDid, 0)
+            .await
+            .unwrap();
+        let document = resolver.resolve(&did).await.unwrap();
+        assert_ne!(document.metadata.deactivated, Some(true));
+        assert_eq!(document.methods(None).len(), 1);
+
+        did_manager.run_action(&Action::DeleteDid, 0).await.unwrap();
+        assert!(resolver.resolve(&did).await.is_err());
+        assert!(did_manager.did(0).is_none());
+    }
+}
@@ -0,0 +1,246 @@
+use std::fs::read_to_string;
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Context};
+use iota_sdk::client::{node_manager::node::NodeAuth, Client};
+use serde::{Deserialize, Serialize};
+
+/// A Tangle network the tests can run against.
+///
+/// The built-in networks can be extended or overridden with a networks file:
+///
+/// ```toml
+/// [[networks]]
+/// id = "TangleA"
+/// name = "Tangle A"
+/// api_endpoints = ["http://10.0.0.5:14265", "http://10.0.0.5:14266"]
+/// faucet_endpoint = "http://10.0.0.5:8091/api/enqueue"
+/// auth_token = "eyJhbGciOi..."
+/// network_name = "testnet"
+/// bech32_hrp = "tst"
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct IotaTangleNetwork {
+    /// Key used on the command line, in scenario files and in the raw data files.
+    pub id: String,
+    pub name: String,
+    pub api_endpoints: Vec<String>,
+    pub faucet_endpoint: String,
+    /// JWT sent to the nodes.
+    #[serde(default)]
+    pub auth_token: Option<String>,
+    /// Expected network name reported by the nodes.
+    #[serde(default)]
+    pub network_name: Option<String>,
+    /// Expected bech32 HRP reported by the nodes.
+    #[serde(default)]
+    pub bech32_hrp: Option<String>,
+}
+
+impl IotaTangleNetwork {
+    fn builtin(id: &str, name: &str, api_endpoint: &str, faucet_endpoint: &str) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            api_endpoints: vec![api_endpoint.to_string()],
+            faucet_endpoint: faucet_endpoint.to_string(),
+            auth_token: None,
+            network_name: None,
+            bech32_hrp: None,
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn faucet_endpoint(&self) -> &str {
+        &self.faucet_endpoint
+    }
+
+    pub fn node_auth(&self) -> Option<NodeAuth> {
+        self.auth_token.as_ref().map(|token| NodeAuth {
+            jwt: Some(token.clone()),
+            basic_auth_name_pwd: None,
+        })
+    }
+
+    /// Checks that the nodes belong to the expected network.
+    pub async fn verify(&self, client: &Client) -> anyhow::Result<()> {
+        if let Some(expected) = &self.network_name {
+            let network_name = client.get_network_name().await?;
+            if &network_name != expected {
+                bail!(
+                    "{}: expected network name `{}`, node reports `{}`",
+                    self.name,
+                    expected,
+                    network_name
+                );
+            }
+        }
+
+        if let Some(expected) = &self.bech32_hrp {
+            let hrp = client.get_bech32_hrp().await?.to_string();
+            if &hrp != expected {
+                bail!(
+                    "{}: expected bech32 HRP `{}`, node reports `{}`",
+                    self.name,
+                    expected,
+                    hrp
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NetworksFile {
+    networks: Vec<IotaTangleNetwork>,
+}
+
+/// All networks known to the application.
+#[derive(Debug, Clone)]
+pub struct Networks {
+    networks: Vec<IotaTangleNetwork>,
+}
+
+impl Networks {
+    pub fn builtin() -> Self {
+        Self {
+            networks: vec![
+                IotaTangleNetwork::builtin(
+                    "Localhost",
+                    "Localhost",
+                    "http://localhost:14265",
+                    "http://localhost:8091/api/enqueue",
+                ),
+                IotaTangleNetwork::builtin(
+                    "Localhost2",
+                    "Localhost 2",
+                    "http://localhost:14266",
+                    "http://localhost:8091/api/enqueue",
+                ),
+                IotaTangleNetwork {
+                    id: "PrivateTangle".to_string(),
+                    name: "Private tangle".to_string(),
+                    api_endpoints: vec![
+                        "http://localhost:14265".to_string(),
+                        "http://localhost:14266".to_string(),
+                        "http://localhost:14267".to_string(),
+                        "http://localhost:14268".to_string(),
+                    ],
+                    faucet_endpoint: "http://localhost:8091/api/enqueue".to_string(),
+                    auth_token: None,
+                    network_name: None,
+                    bech32_hrp: None,
+                },
+                IotaTangleNetwork {
+                    id: "Mock".to_string(),
+                    name: "Mock node".to_string(),
+                    api_endpoints: vec!["http://localhost:14365".to_string()],
+                    faucet_endpoint: "http://localhost:14365/api/enqueue".to_string(),
+                    auth_token: None,
+                    network_name: Some("mock".to_string()),
+                    bech32_hrp: Some("tst".to_string()),
+                },
+                IotaTangleNetwork::builtin(
+                    "IotaTestnet",
+                    "Iota testnet",
+                    "https://api.testnet.iotaledger.net",
+                    "https://faucet.testnet.iotaledger.net/api/enqueue",
+                ),
+                IotaTangleNetwork::builtin(
+                    "ShimmerTestnet",
+                    "Shimmer testnet",
+                    "https://api.testnet.shimmer.network",
+                    "https://faucet.testnet.shimmer.network/api/enqueue",
+                ),
+                IotaTangleNetwork::builtin(
+                    "IotaTestnet2_0",
+                    "IOTA 2.0 testnet",
+                    "https://api.nova-testnet.iotaledger.net/",
+                    "https://faucet.nova-testnet.iotaledger.net/api/enqueue",
+                ),
+            ],
+        }
+    }
+
+    /// Returns the built-in networks extended with the networks in `path`.
+    /// A network in the file replaces the built-in network with the same id.
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let content = read_to_string(path)
+            .with_context(|| format!("failed to read networks file {}", path.display()))?;
+        let file: NetworksFile = toml::from_str(&content)
+            .with_context(|| format!("invalid networks file {}", path.display()))?;
+
+        let mut networks = Self::builtin();
+        for network in file.networks {
+            if network.api_endpoints.is_empty() {
+                bail!(
+                    "invalid networks file {}: network `{}` has no api_endpoints",
+                    path.display(),
+                    network.id
+                );
+            }
+
+            match networks.find(&network.id) {
+                Some(index) => networks.networks[index] = network,
+                None => networks.networks.push(network),
+            }
+        }
+        Ok(networks)
+    }
+
+    /// Looks up a network by id. The comparison ignores case, `-` and `_`,
+    /// so `iota-testnet` selects `IotaTestnet`.
+    pub fn get(&self, id: &str) -> anyhow::Result<IotaTangleNetwork> {
+        self.find(id)
+            .map(|index| self.networks[index].clone())
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown network `{}`, expected one of: {}",
+                    id,
+                    self.networks
+                        .iter()
+                        .map(|network| network.id.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                )
+            })
+    }
+
+    pub fn get_all(&self, ids: &[String]) -> anyhow::Result<Vec<IotaTangleNetwork>> {
+        ids.iter().map(|id| self.get(id)).collect()
+    }
+
+    /// Display name of the network with the given id, or the id itself for unknown networks.
+    /// A label after the id, as in `localhost [memory]`, is kept.
+    pub fn name_of(&self, id: &str) -> String {
+        let (network_id, label) = match id.split_once(" [") {
+            Some((network_id, label)) => (network_id, format!(" [{}", label)),
+            None => (id, String::new()),
+        };
+        match self.find(network_id) {
+            Some(index) => format!("{}{}", self.networks[index].name, label),
+            None => id.to_string(),
+        }
+    }
+
+    fn find(&self, id: &str) -> Option<usize> {
+        let key = normalize_id(id);
+        self.networks
+            .iter()
+            .position(|network| normalize_id(&network.id) == key)
+    }
+}
+
+fn normalize_id(id: &str) -> String {
+    id.chars()
+        .filter(|c| *c != '-' && *c != '_')
+        .flat_map(|c| c.to_lowercase())
+        .collect()
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use iota_sdk::client::{node_manager::node::NodeAuth, Client, ClientBuilder};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::network::IotaTangleNetwork;
+
+/// Consecutive failures after which the primary+fallback strategy skips a node.
+const FAILURE_THRESHOLD: usize = 3;
+
+/// Weight of the newest sample in the exponentially weighted moving average of the latency.
+const LATENCY_SMOOTHING: f64 = 0.2;
+
+/// How a [`NodePool`] picks the node for the next request.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+pub enum NodeStrategy {
+    /// Always use the first node, unless it failed several times in a row.
+    #[default]
+    PrimaryFallback,
+    RoundRobin,
+    /// Use the node with the lowest moving average latency.
+    LeastLatency,
+    Random,
+}
+
+/// Requests, failures and latency of one node of a pool.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NodeUsage {
+    pub requests: usize,
+    pub failures: usize,
+    pub total_duration: f64,
+}
+
+impl NodeUsage {
+    pub fn merge(&mut self, other: &NodeUsage) {
+        self.requests += other.requests;
+        self.failures += other.failures;
+        self.total_duration += other.total_duration;
+    }
+
+    pub fn mean_duration(&self) -> f64 {
+        if self.requests == 0 {
+            0.0
+        } else {
+            self.total_duration / self.requests as f64
+        }
+    }
+}
+
+struct PoolNode {
+    url: String,
+    client: Client,
+    consecutive_failures: AtomicUsize,
+    /// Moving average latency in seconds, `None` until the first request finished.
+    latency: Mutex<Option<f64>>,
+    usage: Mutex<NodeUsage>,
+}
+
+/// One client per node, with a strategy deciding which node serves the next request.
+pub struct NodePool {
+    nodes: Vec<PoolNode>,
+    strategy: NodeStrategy,
+    next: AtomicUsize,
+}
+
+impl NodePool {
+    /// Creates a pool over all API endpoints of `network`.
+    pub async fn from_network(
+        network: &IotaTangleNetwork,
+        strategy: NodeStrategy,
+    ) -> anyhow::Result<Self> {
+        let nodes = network
+            .api_endpoints
+            .iter()
+            .map(|url| (url.clone(), network.node_auth()))
+            .collect();
+        Self::new(nodes, strategy, |builder| builder).await
+    }
+
+    /// Creates a pool over the given nodes. `configure` is applied to the builder of every client.
+    pub async fn new(
+        nodes: Vec<(String, Option<NodeAuth>)>,
+        strategy: NodeStrategy,
+        configure: impl Fn(ClientBuilder) -> ClientBuilder,
+    ) -> anyhow::Result<Self> {
+        if nodes.is_empty() {
+            anyhow::bail!("a node pool needs at least one node");
+        }
+
+        let mut pool_nodes = Vec::with_capacity(nodes.len());
+        for (url, auth) in nodes {
+            let client = configure(Client::builder().with_primary_node(&url, auth)?)
+                .finish()
+                .await?;
+            pool_nodes.push(PoolNode {
+                url,
+                client,
+                consecutive_failures: AtomicUsize::new(0),
+                latency: Mutex::new(None),
+                usage: Mutex::new(NodeUsage::default()),
+            });
+        }
+
+        Ok(Self {
+            nodes: pool_nodes,
+            strategy,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns the index of the node that should serve the next request.
+    pub fn select(&self) -> usize {
+        match self.strategy {
+            NodeStrategy::PrimaryFallback => self
+                .nodes
+                .iter()
+                .position(|node| {
+                    node.consecutive_failures.load(Ordering::Relaxed) < FAILURE_THRESHOLD
+                })
+                .unwrap_or_else(|| {
+                    // All nodes failed, start over with the primary node
+                    for node in &self.nodes {
+                        node.consecutive_failures.store(0, Ordering::Relaxed);
+                    }
+                    0
+                }),
+            NodeStrategy::RoundRobin => {
+                self.next.fetch_add(1, Ordering::Relaxed) % self.nodes.len()
+            }
+            NodeStrategy::LeastLatency => self
+                .nodes
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| {
+                    let a = a.latency.lock().unwrap().unwrap_or(0.0);
+                    let b = b.latency.lock().unwrap().unwrap_or(0.0);
+                    a.total_cmp(&b)
+                })
+                .map(|(index, _)| index)
+                .unwrap_or(0),
+            NodeStrategy::Random => rand::thread_rng().gen_range(0..self.nodes.len()),
+        }
+    }
+
+    pub fn client(&self, node: usize) -> &Client {
+        &self.nodes[node].client
+    }
+
+    pub fn url(&self, node: usize) -> &str {
+        &self.nodes[node].url
+    }
+
+    /// Records the outcome of a request served by `node`.
+    pub fn record(&self, node: usize, duration: Duration, success: bool) {
+        let node = &self.nodes[node];
+        let seconds = duration.as_secs_f64();
+
+        if success {
+            node.consecutive_failures.store(0, Ordering::Relaxed);
+        } else {
+            node.consecutive_failures.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut latency = node.latency.lock().unwrap();
+        *latency = Some(match *latency {
+            Some(average) => average + LATENCY_SMOOTHING * (seconds - average),
+            None => seconds,
+        });
+
+        let mut usage = node.usage.lock().unwrap();
+        usage.requests += 1;
+        usage.total_duration += seconds;
+        if !success {
+            usage.failures += 1;
+        }
+    }
+
+    /// Requests served per node URL.
+    pub fn usage(&self) -> HashMap<String, NodeUsage> {
+        self.nodes
+            .iter()
+            .map(|node| (node.url.clone(), node.usage.lock().unwrap().clone()))
+            .collect()
+    }
+}
+
+pub fn merge_node_usage(
+    total: &mut HashMap<String, NodeUsage>,
+    other: &HashMap<String, NodeUsage>,
+) {
+    for (url, usage) in other {
+        total.entry(url.clone()).or_default().merge(usage);
+    }
+}
+
+pub fn print_node_usage(usage: &HashMap<String, NodeUsage>) {
+    let total_requests: usize = usage.values().map(|usage| usage.requests).sum();
+
+    println!(
+        "{0: <30} | {1: <10} | {2: <10} | {3: <10} | {4: <10}",
+        "Node", "Requests", "Share", "Failures", "Mean"
+    );
+    let mut urls: Vec<&String> = usage.keys().collect();
+    urls.sort();
+    for url in urls {
+        let node_usage = &usage[url];
+        let share = if total_requests == 0 {
+            0.0
+        } else {
+            node_usage.requests as f64 / total_requests as f64 * 100.0
+        };
+        println!(
+            "{0: <30} | {1: <10} | {2: <9.1}% | {3: <10} | {4: <10.4}",
+            url,
+            node_usage.requests,
+            share,
+            node_usage.failures,
+            node_usage.mean_duration(),
+        );
+    }
+}
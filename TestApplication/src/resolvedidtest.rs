@@ -1,27 +1,33 @@
+use chrono::Utc;
 use identity_iota::{
     iota::{IotaDID, IotaDocument},
     prelude::Resolver,
 };
-use iota_sdk::client::Client;
 use log::{info, warn};
 use rand::Rng;
+use std::collections::HashMap;
 
 use crate::{
     didmanager::DIDManager,
+    failure::FailureKind,
     graph::{draw_action_measurements, get_and_create_folder},
-    utils::{Action, IotaTangleNetwork, Measurement},
+    keystorage::KeyStorageConfig,
+    network::IotaTangleNetwork,
+    nodepool::{merge_node_usage, print_node_usage, NodePool, NodeStrategy, NodeUsage},
+    utils::{Action, Measurement, Sample},
 };
 use tokio::task;
 use tokio::time::{sleep, Duration, Instant};
 
-pub async fn resolve_did_test() {
-    // Stronghold snapshot path.
-    match DIDManager::new(
-        IotaTangleNetwork::Localhost.api_endpoint(),
-        IotaTangleNetwork::Localhost.faucet_endpoint(),
-    )
-    .await
-    {
+pub async fn resolve_did_test(
+    network: &IotaTangleNetwork,
+    node_strategy: NodeStrategy,
+    key_storage: &KeyStorageConfig,
+    num_threads: usize,
+    iterations: usize,
+) {
+    // Resolving does not use the keys, so the first backend is enough
+    match DIDManager::new(network, node_strategy, key_storage, key_storage.backends[0]).await {
         Ok(mut did_manager) => {
             let index = 0;
             let _ = did_manager.create_did(index).await;
@@ -29,19 +35,25 @@ pub async fn resolve_did_test() {
             let did_information = did_manager.did_map.get(&index).unwrap();
             let did: IotaDID = did_information.did.clone();
             info!("DID {did}");
-            let num_threads = 50;
-            let iterations = 100;
             let mut measurement = Measurement::new();
+            let mut node_usage = HashMap::new();
+
+            spawn_tasks(
+                &mut measurement,
+                &mut node_usage,
+                num_threads,
+                iterations,
+                network,
+                node_strategy,
+                did,
+            )
+            .await;
 
-            spawn_tasks(&mut measurement, num_threads, iterations, did).await;
+            print_node_usage(&node_usage);
 
             let folder_name = get_and_create_folder().unwrap();
 
-            draw_action_measurements(
-                &IotaTangleNetwork::Localhost.name(),
-                &measurement,
-                &folder_name,
-            );
+            draw_action_measurements(network.name(), &measurement, &folder_name);
         }
         Err(e) => {
             warn!("Failed to create DIDManager: {:?}", e);
@@ -51,59 +63,77 @@ pub async fn resolve_did_test() {
 
 async fn spawn_tasks(
     measurements: &mut Measurement,
+    node_usage: &mut HashMap<String, NodeUsage>,
     num_threads: usize,
     iterations: usize,
+    network: &IotaTangleNetwork,
+    node_strategy: NodeStrategy,
     did: IotaDID,
 ) {
     let mut handles = vec![];
     let test_start = Instant::now();
 
-    for _ in 0..num_threads {
+    for task_id in 0..num_threads {
         let iterations = iterations.clone();
+        let network = network.clone();
         let did = did.clone();
         let random_delay = rand::thread_rng().gen_range(5..=20);
         sleep(Duration::from_millis(random_delay)).await; // Add random delay to simulate users before starting each thread
 
         let handle = task::spawn(async move {
             let mut measurement = Measurement::new();
-            match Client::builder()
-                .with_primary_node(IotaTangleNetwork::Localhost.api_endpoint(), None)
-            {
-                Ok(builder) => match builder.finish().await {
-                    Ok(client) => {
-                        // Successfully created the client, you can use the client here
-
-                        let mut resolver = Resolver::<IotaDocument>::new();
-                        resolver.attach_iota_handler(client.clone());
-
-                        let action_measurements = measurement
-                            .entry(Action::ResolveDid)
-                            .or_insert_with(Vec::new);
-
-                        for _ in 0..iterations {
-                            let start = Instant::now();
-
-                            match resolver.resolve(&did).await {
-                                Ok(resolved_document) => {
-                                    assert_eq!(did, *resolved_document.id());
-                                    let duration = start.elapsed();
-                                    action_measurements.push(duration.as_secs_f64());
-                                }
-                                Err(e) => {
-                                    warn!("Error: {:?}", e);
-                                }
-                            };
-                        }
+            let mut node_usage = HashMap::new();
+            match NodePool::from_network(&network, node_strategy).await {
+                Ok(pool) => {
+                    // One resolver per node of the pool
+                    let resolvers: Vec<Resolver<IotaDocument>> = (0..pool.len())
+                        .map(|node| {
+                            let mut resolver = Resolver::<IotaDocument>::new();
+                            resolver.attach_iota_handler(pool.client(node).clone());
+                            resolver
+                        })
+                        .collect();
+
+                    let action_measurements = measurement
+                        .entry(Action::ResolveDid)
+                        .or_insert_with(Vec::new);
+
+                    for iteration in 0..iterations {
+                        let node = pool.select();
+                        let start_time = Utc::now();
+                        let start = Instant::now();
+
+                        match resolvers[node].resolve(&did).await {
+                            Ok(resolved_document) => {
+                                assert_eq!(did, *resolved_document.id());
+                                let duration = start.elapsed();
+                                pool.record(node, duration, true);
+                                action_measurements.push(
+                                    Sample::new(start_time, duration, task_id, iteration)
+                                        .with_node(pool.url(node)),
+                                );
+                            }
+                            Err(e) => {
+                                let duration = start.elapsed();
+                                pool.record(node, duration, false);
+                                let e = anyhow::Error::from(e);
+                                warn!("Error on {}: {:?}", pool.url(node), e);
+                                action_measurements.push(
+                                    Sample::new(start_time, duration, task_id, iteration)
+                                        .with_node(pool.url(node))
+                                        .failed(FailureKind::classify(&e)),
+                                );
+                            }
+                        };
                     }
-                    Err(e) => {
-                        warn!("Error creating the client: {:?}", e);
-                    }
-                },
+
+                    node_usage = pool.usage();
+                }
                 Err(e) => {
-                    warn!("Error adding primary node: {:?}", e);
+                    warn!("Error creating the node pool: {:?}", e);
                 }
             };
-            measurement
+            (measurement, node_usage)
         });
 
         handles.push(handle);
@@ -115,11 +145,12 @@ async fn spawn_tasks(
     // Await all the tasks to complete
     for handle in handles {
         match handle.await {
-            Ok(mut result) => {
+            Ok((mut result, task_node_usage)) => {
                 for (action, durations) in &mut result {
                     let element = measurements.entry(*action).or_insert_with(Vec::new);
                     element.append(durations);
                 }
+                merge_node_usage(node_usage, &task_node_usage);
             }
             Err(err) => {
                 warn!("Invalid thread results: {:?}", err);
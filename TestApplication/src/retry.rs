@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::failure::FailureKind;
+
+/// When and how often a failed DID operation is tried again.
+/// Retries wait with exponential backoff: `initial_backoff * multiplier^retry`, capped at `max_backoff`,
+/// of which up to a `jitter` share is randomly removed so concurrent tasks do not retry in lockstep.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts per operation, including the first one. 1 disables retries.
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    /// Share of the backoff that is randomized, between 0 and 1.
+    pub jitter: f64,
+    /// Failure kinds that are retried. Any other failure is final.
+    pub retry_on: Vec<FailureKind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            multiplier: 2.0,
+            jitter: 0.5,
+            retry_on: Self::transient_failures(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Failures that usually succeed when the operation is tried again.
+    pub fn transient_failures() -> Vec<FailureKind> {
+        vec![
+            FailureKind::ConflictingOutput,
+            FailureKind::NodeServerError,
+            FailureKind::NodeUnreachable,
+        ]
+    }
+
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.max_attempts == 0 {
+            anyhow::bail!("max_attempts must be at least 1");
+        }
+        if self.multiplier < 1.0 {
+            anyhow::bail!("multiplier must be at least 1, got {}", self.multiplier);
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            anyhow::bail!("jitter must be between 0 and 1, got {}", self.jitter);
+        }
+        Ok(())
+    }
+
+    /// Whether the operation is tried again after its `attempt`-th attempt (starting at 1) failed with `kind`.
+    pub fn should_retry(&self, attempt: u32, kind: FailureKind) -> bool {
+        attempt < self.max_attempts && self.retry_on.contains(&kind)
+    }
+
+    /// Time to wait before the `retry`-th retry, starting at 1.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(retry as i32 - 1);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        let jitter = backoff * self.jitter * rand::thread_rng().gen::<f64>();
+        Duration::from_secs_f64(backoff - jitter)
+    }
+}
+
+/// Retries of the last operation.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Retries {
+    pub count: u32,
+    /// Time spent in the failed attempts and the backoff before the last attempt.
+    pub time: Duration,
+}
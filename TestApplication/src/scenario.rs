@@ -0,0 +1,324 @@
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+use crate::consistency::ConsistencyConfig;
+use crate::failure::FailureKind;
+use crate::keystorage::{KeyAlgorithm, KeyStorageConfig, StorageBackend};
+use crate::network::{IotaTangleNetwork, Networks};
+use crate::nodepool::NodeStrategy;
+use crate::retry::RetryPolicy;
+use crate::utils::Action;
+use crate::Params;
+
+/// A complete DID benchmark experiment, loaded from a TOML or YAML file.
+///
+/// ```toml
+/// networks = ["Localhost"]
+/// output_dir = "test/localhost_sweep"
+/// node_strategy = "round-robin"
+/// confirmation = true
+/// track_cost = true
+/// state_dir = "state/localhost_sweep"
+/// storage = ["stronghold", "memory"]
+/// key_algorithms = ["ed25519"]
+///
+/// [[params]]
+/// num_threads = 5
+/// iterations = 100
+///
+/// [[actions]]
+/// action = "create-did"
+///
+/// [[actions]]
+/// action = "update-did"
+/// iterations = 10
+///
+/// [retry]
+/// max_attempts = 3
+/// initial_backoff_ms = 500
+/// retry_on = ["conflicting-output", "node-server-error"]
+///
+/// [consistency]
+/// cross_node = true
+/// timeout_ms = 60000
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scenario {
+    pub networks: Vec<IotaTangleNetwork>,
+    pub params: Vec<Params>,
+    pub actions: Vec<ScenarioAction>,
+    pub output_dir: String,
+    /// How each task spreads its requests over the API endpoints of a network.
+    pub node_strategy: NodeStrategy,
+    /// How failed DID operations are retried.
+    pub retry_policy: RetryPolicy,
+    /// Time every action until its block is included by a milestone and the new state resolves.
+    pub confirmation: bool,
+    /// Query the wallet balance before and after every action that changes a DID.
+    pub track_cost: bool,
+    /// Verify the state of a DID after every change. `None` disables the check.
+    pub consistency: Option<ConsistencyConfig>,
+    /// Folder with one state file per network and task, to continue with the DIDs of earlier runs.
+    pub state_dir: Option<PathBuf>,
+    /// Where the keys are kept. The stronghold password is never read from the scenario file.
+    pub key_storage: KeyStorageConfig,
+    /// File the scenario was loaded from. It is copied next to the results.
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ScenarioAction {
+    pub action: Action,
+    /// Overrides `Params::iterations` for this action.
+    pub iterations: Option<usize>,
+}
+
+impl ScenarioAction {
+    pub fn new(action: Action) -> Self {
+        Self {
+            action,
+            iterations: None,
+        }
+    }
+
+    pub fn iterations(&self, params: &Params) -> usize {
+        self.iterations.unwrap_or(params.iterations)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ScenarioFile {
+    networks: Vec<String>,
+    params: Vec<ParamsEntry>,
+    actions: Vec<ActionEntry>,
+    #[serde(default = "default_output_dir")]
+    output_dir: String,
+    node_strategy: Option<String>,
+    retry: Option<RetryEntry>,
+    #[serde(default)]
+    confirmation: bool,
+    #[serde(default)]
+    track_cost: bool,
+    consistency: Option<ConsistencyEntry>,
+    state_dir: Option<PathBuf>,
+    storage: Option<Vec<String>>,
+    key_algorithms: Option<Vec<String>>,
+    #[serde(default)]
+    keep_strongholds: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ParamsEntry {
+    num_threads: usize,
+    iterations: usize,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ActionEntry {
+    action: String,
+    iterations: Option<usize>,
+}
+
+/// Retry policy of a scenario. Missing fields keep the values of [`RetryPolicy::default`].
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RetryEntry {
+    max_attempts: Option<u32>,
+    initial_backoff_ms: Option<u64>,
+    max_backoff_ms: Option<u64>,
+    multiplier: Option<f64>,
+    jitter: Option<f64>,
+    retry_on: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConsistencyEntry {
+    #[serde(default)]
+    cross_node: bool,
+    timeout_ms: Option<u64>,
+}
+
+fn default_output_dir() -> String {
+    "test".to_string()
+}
+
+impl Scenario {
+    pub fn load(path: &Path, known_networks: &Networks) -> anyhow::Result<Self> {
+        let content = read_to_string(path)
+            .with_context(|| format!("failed to read scenario file {}", path.display()))?;
+
+        let file: ScenarioFile = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => toml::from_str(&content)
+                .with_context(|| format!("invalid scenario file {}", path.display()))?,
+            Some("yaml") | Some("yml") => serde_yaml::from_str(&content)
+                .with_context(|| format!("invalid scenario file {}", path.display()))?,
+            _ => bail!(
+                "unsupported scenario file {}, expected a .toml, .yaml or .yml file",
+                path.display()
+            ),
+        };
+
+        let mut scenario = Self::from_file(file, known_networks)
+            .and_then(|scenario| scenario.validate().map(|()| scenario))
+            .with_context(|| format!("invalid scenario file {}", path.display()))?;
+        scenario.source = Some(path.to_path_buf());
+        Ok(scenario)
+    }
+
+    /// Checks the settings that cannot be combined or would only fail once the tasks run.
+    /// Scenarios from the command line are checked the same way as scenario files.
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.networks.is_empty() {
+            bail!("no networks given");
+        }
+        if self.params.is_empty() {
+            bail!("no params given");
+        }
+        if self.actions.is_empty() {
+            bail!("no actions given");
+        }
+        if self.params.iter().any(|params| params.num_threads == 0) {
+            bail!("num_threads must be at least 1");
+        }
+        if self.state_dir.is_some() && self.key_storage.backends.contains(&StorageBackend::Memory) {
+            bail!("saved DIDs need the stronghold backend, the memory backend cannot be combined with state_dir");
+        }
+        Ok(())
+    }
+
+    fn from_file(file: ScenarioFile, known_networks: &Networks) -> anyhow::Result<Self> {
+        let networks = known_networks.get_all(&file.networks)?;
+
+        let params = file
+            .params
+            .iter()
+            .map(|entry| Params {
+                num_threads: entry.num_threads,
+                iterations: entry.iterations,
+            })
+            .collect();
+
+        let actions = file
+            .actions
+            .iter()
+            .map(|entry| {
+                Ok(ScenarioAction {
+                    action: parse_value::<Action>("action", &entry.action)?,
+                    iterations: entry.iterations,
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let node_strategy = match &file.node_strategy {
+            Some(name) => parse_value::<NodeStrategy>("node strategy", name)?,
+            None => NodeStrategy::default(),
+        };
+
+        let backends = parse_values::<StorageBackend>("storage backend", &file.storage)?;
+        let algorithms = parse_values::<KeyAlgorithm>("key algorithm", &file.key_algorithms)?;
+
+        let retry_policy = match &file.retry {
+            Some(entry) => parse_retry_policy(entry)?,
+            None => RetryPolicy::default(),
+        };
+
+        Ok(Self {
+            networks,
+            params,
+            actions,
+            output_dir: file.output_dir,
+            node_strategy,
+            retry_policy,
+            confirmation: file.confirmation,
+            track_cost: file.track_cost,
+            consistency: file.consistency.as_ref().map(|entry| {
+                let default = ConsistencyConfig::default();
+                ConsistencyConfig {
+                    cross_node: entry.cross_node,
+                    timeout: entry
+                        .timeout_ms
+                        .map(Duration::from_millis)
+                        .unwrap_or(default.timeout),
+                    ..default
+                }
+            }),
+            state_dir: file.state_dir,
+            key_storage: KeyStorageConfig {
+                backends,
+                algorithms,
+                password: None,
+                keep_strongholds: file.keep_strongholds,
+            },
+            source: None,
+        })
+    }
+}
+
+fn parse_retry_policy(entry: &RetryEntry) -> anyhow::Result<RetryPolicy> {
+    let default = RetryPolicy::default();
+    let retry_on = match &entry.retry_on {
+        Some(names) => names
+            .iter()
+            .map(|name| parse_value::<FailureKind>("failure kind", name))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+        None => default.retry_on,
+    };
+
+    let retry_policy = RetryPolicy {
+        max_attempts: entry.max_attempts.unwrap_or(default.max_attempts),
+        initial_backoff: entry
+            .initial_backoff_ms
+            .map(Duration::from_millis)
+            .unwrap_or(default.initial_backoff),
+        max_backoff: entry
+            .max_backoff_ms
+            .map(Duration::from_millis)
+            .unwrap_or(default.max_backoff),
+        multiplier: entry.multiplier.unwrap_or(default.multiplier),
+        jitter: entry.jitter.unwrap_or(default.jitter),
+        retry_on,
+    };
+    retry_policy.validate()?;
+    Ok(retry_policy)
+}
+
+fn parse_value<T: ValueEnum>(kind: &str, name: &str) -> anyhow::Result<T> {
+    T::from_str(name, true).map_err(|_| {
+        anyhow!(
+            "unknown {} `{}`, expected one of: {}",
+            kind,
+            name,
+            possible_values::<T>()
+        )
+    })
+}
+
+/// Parses a list of values, or returns the default value if the list is not given.
+fn parse_values<T: ValueEnum + Default>(
+    kind: &str,
+    names: &Option<Vec<String>>,
+) -> anyhow::Result<Vec<T>> {
+    match names {
+        Some(names) if names.is_empty() => bail!("no {} given", kind),
+        Some(names) => names.iter().map(|name| parse_value(kind, name)).collect(),
+        None => Ok(vec![T::default()]),
+    }
+}
+
+fn possible_values<T: ValueEnum>() -> String {
+    T::value_variants()
+        .iter()
+        .filter_map(|value| value.to_possible_value())
+        .map(|value| value.get_name().to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
@@ -0,0 +1,326 @@
+use hdrhistogram::Histogram;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use statrs::distribution::{ContinuousCDF, Normal, StudentsT};
+use statrs::statistics::Statistics;
+
+/// Lowest and highest trackable duration in microseconds (1 µs to 1 h).
+const HISTOGRAM_LOW: u64 = 1;
+const HISTOGRAM_HIGH: u64 = 3_600_000_000;
+/// Significant decimal digits kept by the histogram.
+const HISTOGRAM_SIGFIG: u8 = 3;
+
+/// Durations recorded with microsecond resolution.
+/// Histograms of different tasks are merged with [`LatencyHistogram::merge`].
+#[derive(Debug, Clone)]
+pub struct LatencyHistogram {
+    histogram: Histogram<u64>,
+}
+
+impl LatencyHistogram {
+    pub fn new() -> Self {
+        Self {
+            histogram: Histogram::new_with_bounds(HISTOGRAM_LOW, HISTOGRAM_HIGH, HISTOGRAM_SIGFIG)
+                .expect("valid histogram bounds"),
+        }
+    }
+
+    pub fn from_durations(durations: &[f64]) -> Self {
+        let mut histogram = Self::new();
+        for duration in durations {
+            histogram.record(*duration);
+        }
+        histogram
+    }
+
+    /// Records a duration in seconds. Durations outside the trackable range are clamped.
+    pub fn record(&mut self, duration: f64) {
+        self.histogram
+            .saturating_record((duration * 1_000_000.0).round().max(0.0) as u64);
+    }
+
+    pub fn merge(&mut self, other: &LatencyHistogram) {
+        // Both histograms have the same bounds, so the addition cannot fail
+        self.histogram
+            .add(&other.histogram)
+            .expect("histograms with equal bounds");
+    }
+
+    /// Duration in seconds at the given quantile, between 0 and 1.
+    pub fn quantile(&self, quantile: f64) -> f64 {
+        self.histogram.value_at_quantile(quantile) as f64 / 1_000_000.0
+    }
+}
+
+/// Summary of a set of durations in seconds.
+/// Min, max, mean, variance and the deviations are exact, the percentiles come from a [`LatencyHistogram`].
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct Stats {
+    pub count: usize,
+    pub min: f64,
+    pub max: f64,
+    pub mean: f64,
+    pub variance: f64,
+    pub std_dev: f64,
+    /// Median absolute deviation.
+    pub mad: f64,
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub p999: f64,
+}
+
+impl Stats {
+    /// Percentiles from `histogram`, the other values from `numbers`.
+    pub fn new(numbers: &[f64], histogram: &LatencyHistogram) -> Self {
+        if numbers.is_empty() {
+            return Stats::default();
+        }
+
+        Stats {
+            count: numbers.len(),
+            min: numbers.min(),
+            max: numbers.max(),
+            mean: numbers.mean(),
+            variance: numbers.variance(),
+            std_dev: numbers.std_dev(),
+            mad: median_absolute_deviation(numbers),
+            p50: histogram.quantile(0.5),
+            p90: histogram.quantile(0.9),
+            p95: histogram.quantile(0.95),
+            p99: histogram.quantile(0.99),
+            p999: histogram.quantile(0.999),
+        }
+    }
+}
+
+pub fn calculate_stats(numbers: &[f64]) -> Stats {
+    Stats::new(numbers, &LatencyHistogram::from_durations(numbers))
+}
+
+fn median(sorted: &[f64]) -> f64 {
+    let middle = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[middle - 1] + sorted[middle]) / 2.0
+    } else {
+        sorted[middle]
+    }
+}
+
+fn median_absolute_deviation(numbers: &[f64]) -> f64 {
+    let mut sorted = numbers.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let center = median(&sorted);
+
+    let mut deviations: Vec<f64> = sorted.iter().map(|x| (x - center).abs()).collect();
+    deviations.sort_by(f64::total_cmp);
+    median(&deviations)
+}
+
+/// Resamples drawn for a bootstrap confidence interval.
+const BOOTSTRAP_RESAMPLES: usize = 2000;
+/// Fixed seed, so the intervals of a raw data file are the same on every report.
+const BOOTSTRAP_SEED: u64 = 5000;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default)]
+pub struct ConfidenceInterval {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+/// Percentile bootstrap confidence interval of `statistic` over `numbers`.
+/// `confidence` is the coverage, e.g. 0.95.
+pub fn bootstrap_ci(
+    numbers: &[f64],
+    statistic: impl Fn(&[f64]) -> f64,
+    confidence: f64,
+) -> ConfidenceInterval {
+    if numbers.is_empty() {
+        return ConfidenceInterval::default();
+    }
+
+    let mut rng = StdRng::seed_from_u64(BOOTSTRAP_SEED);
+    let mut resample = vec![0.0; numbers.len()];
+    let mut estimates: Vec<f64> = (0..BOOTSTRAP_RESAMPLES)
+        .map(|_| {
+            for value in resample.iter_mut() {
+                *value = numbers[rng.gen_range(0..numbers.len())];
+            }
+            statistic(&resample)
+        })
+        .collect();
+    estimates.sort_by(f64::total_cmp);
+
+    let alpha = 1.0 - confidence;
+    ConfidenceInterval {
+        lower: percentile_of_sorted(&estimates, alpha / 2.0),
+        upper: percentile_of_sorted(&estimates, 1.0 - alpha / 2.0),
+    }
+}
+
+pub fn mean_of(numbers: &[f64]) -> f64 {
+    numbers.mean()
+}
+
+/// Exact percentile with linear interpolation, `quantile` between 0 and 1.
+pub fn percentile(numbers: &[f64], quantile: f64) -> f64 {
+    let mut sorted = numbers.to_vec();
+    sorted.sort_by(f64::total_cmp);
+    percentile_of_sorted(&sorted, quantile)
+}
+
+fn percentile_of_sorted(sorted: &[f64], quantile: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let position = quantile.clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * (position - lower as f64)
+}
+
+/// Result of a two-sided two-sample test.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TestResult {
+    pub statistic: f64,
+    pub p_value: f64,
+}
+
+/// Welch's t-test for samples with unequal variances.
+/// `None` if a sample has less than two values or both have no variance.
+pub fn welch_t_test(a: &[f64], b: &[f64]) -> Option<TestResult> {
+    if a.len() < 2 || b.len() < 2 {
+        return None;
+    }
+
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let (var_a, var_b) = (a.variance() / n_a, b.variance() / n_b);
+    let standard_error = (var_a + var_b).sqrt();
+    if standard_error == 0.0 {
+        return None;
+    }
+
+    let t = (a.mean() - b.mean()) / standard_error;
+    // Welch–Satterthwaite equation
+    let freedom =
+        (var_a + var_b).powi(2) / (var_a.powi(2) / (n_a - 1.0) + var_b.powi(2) / (n_b - 1.0));
+    let distribution = StudentsT::new(0.0, 1.0, freedom).ok()?;
+
+    Some(TestResult {
+        statistic: t,
+        p_value: 2.0 * (1.0 - distribution.cdf(t.abs())),
+    })
+}
+
+/// Mann–Whitney U test with the normal approximation, corrected for ties and continuity.
+/// `None` if a sample is empty or all values are equal.
+pub fn mann_whitney_u(a: &[f64], b: &[f64]) -> Option<TestResult> {
+    if a.is_empty() || b.is_empty() {
+        return None;
+    }
+
+    let mut values: Vec<(f64, bool)> = a
+        .iter()
+        .map(|x| (*x, true))
+        .chain(b.iter().map(|x| (*x, false)))
+        .collect();
+    values.sort_by(|x, y| x.0.total_cmp(&y.0));
+
+    // Average ranks of tied values
+    let n = values.len() as f64;
+    let mut rank_sum_a = 0.0;
+    let mut tie_correction = 0.0;
+    let mut start = 0;
+    while start < values.len() {
+        let mut end = start;
+        while end + 1 < values.len() && values[end + 1].0 == values[start].0 {
+            end += 1;
+        }
+        let ties = (end - start + 1) as f64;
+        let rank = (start + end) as f64 / 2.0 + 1.0;
+        rank_sum_a += rank * values[start..=end].iter().filter(|x| x.1).count() as f64;
+        tie_correction += ties.powi(3) - ties;
+        start = end + 1;
+    }
+
+    let (n_a, n_b) = (a.len() as f64, b.len() as f64);
+    let u_a = rank_sum_a - n_a * (n_a + 1.0) / 2.0;
+    let mean = n_a * n_b / 2.0;
+    let std_dev = (n_a * n_b / 12.0 * ((n + 1.0) - tie_correction / (n * (n - 1.0)))).sqrt();
+    if std_dev == 0.0 || !std_dev.is_finite() {
+        return None;
+    }
+
+    let z = ((u_a - mean).abs() - 0.5).max(0.0) / std_dev;
+    let normal = Normal::new(0.0, 1.0).ok()?;
+
+    Some(TestResult {
+        statistic: u_a.min(n_a * n_b - u_a),
+        p_value: 2.0 * (1.0 - normal.cdf(z)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn median_of_odd_and_even_counts() {
+        assert_eq!(median(&[1.0, 2.0, 7.0]), 2.0);
+        assert_eq!(median(&[1.0, 2.0, 3.0, 10.0]), 2.5);
+    }
+
+    #[test]
+    fn median_absolute_deviation_of_unsorted_numbers() {
+        // Median 2, absolute deviations 0, 0, 1, 1, 2, 4, 7
+        assert_eq!(
+            median_absolute_deviation(&[9.0, 1.0, 2.0, 6.0, 1.0, 4.0, 2.0]),
+            1.0
+        );
+        assert_eq!(median_absolute_deviation(&[3.0, 3.0, 3.0]), 0.0);
+    }
+
+    #[test]
+    fn percentile_interpolates_between_values() {
+        let numbers = [4.0, 1.0, 3.0, 2.0];
+        assert_eq!(percentile(&numbers, 0.0), 1.0);
+        assert_eq!(percentile(&numbers, 0.25), 1.75);
+        assert_eq!(percentile(&numbers, 0.5), 2.5);
+        assert_eq!(percentile(&numbers, 1.0), 4.0);
+        assert!(percentile(&[], 0.5).is_nan());
+    }
+
+    #[test]
+    fn histogram_quantiles_within_precision() {
+        let durations: Vec<f64> = (1..=1000).map(|ms| ms as f64 / 1000.0).collect();
+        let mut histogram = LatencyHistogram::from_durations(&durations[..500]);
+        histogram.merge(&LatencyHistogram::from_durations(&durations[500..]));
+
+        for (quantile, expected) in [(0.5, 0.5), (0.9, 0.9), (0.99, 0.99), (1.0, 1.0)] {
+            let value = histogram.quantile(quantile);
+            assert!(
+                (value - expected).abs() <= expected * 0.001,
+                "p{} is {}, expected {}",
+                quantile * 100.0,
+                value,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn stats_of_durations() {
+        let stats = calculate_stats(&[0.1, 0.2, 0.3, 0.4, 0.5]);
+        assert_eq!(stats.count, 5);
+        assert_eq!(stats.min, 0.1);
+        assert_eq!(stats.max, 0.5);
+        assert!((stats.mean - 0.3).abs() < 1e-12);
+        assert!((stats.mad - 0.1).abs() < 1e-12);
+        assert!((stats.p50 - 0.3).abs() <= 0.3 * 0.001);
+
+        assert_eq!(calculate_stats(&[]).count, 0);
+    }
+}
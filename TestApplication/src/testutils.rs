@@ -1,62 +1,230 @@
+use chrono::Utc;
 use log::{info, warn};
 use serde_json::to_string_pretty;
 use tokio::task;
 use tokio::time::{sleep, Duration, Instant};
 
 use crate::didmanager::DIDManager;
-use crate::graph::{draw_all_measurements, get_and_create_folder};
+use crate::failure::FailureKind;
+use crate::graph::{draw_all_measurements, draw_grouped_box_plot, get_and_create_folder_in};
+use crate::keystorage::KeySetup;
+use crate::network::{IotaTangleNetwork, Networks};
+use crate::nodepool::{merge_node_usage, print_node_usage, NodeUsage};
+use crate::scenario::Scenario;
 use crate::utils::{
-    print_measurement_stats, save_to_raw_data_file, Action, IotaTangleNetwork, Measurement,
+    load_raw_data, print_comparison, print_confidence_intervals, print_measurement_stats,
+    print_run_comparison, save_to_file, save_to_raw_data_file, Action, Measurement, Sample,
 };
+use crate::Params;
 use std::collections::HashMap;
-use std::fs::read_to_string;
+use std::fs;
+use std::path::Path;
 
-pub fn read_and_print_raw_data(file_name: &str) {
+pub fn read_and_print_raw_data(file_name: &str, known_networks: &Networks) {
     println!("{}", file_name);
-    if let Ok(json_data) = read_to_string(file_name) {
-        let all_measurements: HashMap<IotaTangleNetwork, Measurement> =
-            serde_json::from_str(&json_data).unwrap();
+    match load_raw_data(file_name) {
+        Ok(all_measurements) => {
+            for (network_id, measurement) in &all_measurements {
+                println!("Test results for {}", known_networks.name_of(network_id));
+                print_measurement_stats(measurement);
+                print_confidence_intervals(measurement);
+            }
+        }
+        Err(e) => warn!("{:?}", e),
+    }
+}
+
+/// Prints the runs in `paths` side by side, relative to the first run, and draws grouped box plots.
+/// A path is a raw_data file or a results folder. Runs are grouped by network, except when every
+/// run has a single network, e.g. a localhost run and a testnet run, which are then compared directly.
+pub fn compare_runs(
+    paths: &[String],
+    output_dir: &str,
+    known_networks: &Networks,
+) -> anyhow::Result<()> {
+    let runs = paths
+        .iter()
+        .map(|path| Ok((path.trim_end_matches('/').to_string(), load_raw_data(path)?)))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    let mut network_ids: Vec<&String> = runs
+        .iter()
+        .flat_map(|(_, measurements)| measurements.keys())
+        .collect();
+    network_ids.sort();
+    network_ids.dedup();
+
+    let groups: Vec<(String, Vec<(String, &Measurement)>)> =
+        if network_ids.len() > 1 && runs.iter().all(|(_, measurements)| measurements.len() == 1) {
+            let group = runs
+                .iter()
+                .flat_map(|(label, measurements)| {
+                    measurements.iter().map(move |(network_id, measurement)| {
+                        let label = format!("{} ({})", label, known_networks.name_of(network_id));
+                        (label, measurement)
+                    })
+                })
+                .collect();
+            let title = network_ids
+                .iter()
+                .map(|network_id| known_networks.name_of(network_id))
+                .collect::<Vec<_>>()
+                .join(" vs ");
+            vec![(title, group)]
+        } else {
+            network_ids
+                .iter()
+                .map(|network_id| {
+                    let group = runs
+                        .iter()
+                        .filter_map(|(label, measurements)| {
+                            measurements
+                                .get(*network_id)
+                                .map(|measurement| (label.clone(), measurement))
+                        })
+                        .collect();
+                    (known_networks.name_of(network_id), group)
+                })
+                .collect()
+        };
+
+    let folder_name = get_and_create_folder_in(output_dir)?;
+    for (title, group) in &groups {
+        println!("Comparison for {}", title);
+        print_run_comparison(group);
+        draw_grouped_box_plot(&folder_name, title, group);
+    }
+    Ok(())
+}
 
-        for (network, measurement) in &all_measurements {
-            println!("Test results for {}", network.name());
-            print_measurement_stats(measurement);
+/// Compares every action of two raw_data files with Welch's t-test and the Mann–Whitney U test.
+/// Networks present in both files are compared with each other. Files with a single network each,
+/// e.g. a localhost run and a testnet run, are compared directly.
+pub fn compare_raw_data(
+    baseline_file: &str,
+    candidate_file: &str,
+    alpha: f64,
+    known_networks: &Networks,
+) -> anyhow::Result<()> {
+    let baseline = load_raw_data(baseline_file)?;
+    let candidate = load_raw_data(candidate_file)?;
+
+    let mut pairs: Vec<(&String, &String)> = baseline
+        .keys()
+        .filter(|network_id| candidate.contains_key(*network_id))
+        .map(|network_id| (network_id, network_id))
+        .collect();
+    if pairs.is_empty() {
+        match (baseline.keys().next(), candidate.keys().next()) {
+            (Some(a), Some(b)) if baseline.len() == 1 && candidate.len() == 1 => pairs.push((a, b)),
+            _ => anyhow::bail!(
+                "{} and {} have no network in common",
+                baseline_file,
+                candidate_file
+            ),
         }
     }
+    pairs.sort();
+
+    println!("Baseline: {}", baseline_file);
+    println!("Candidate: {}", candidate_file);
+    for (baseline_id, candidate_id) in pairs {
+        println!(
+            "{} vs {}",
+            known_networks.name_of(baseline_id),
+            known_networks.name_of(candidate_id)
+        );
+        print_comparison(&baseline[baseline_id], &candidate[candidate_id], alpha);
+    }
+    Ok(())
+}
+
+pub async fn run_scenario(scenario: &Scenario, known_networks: &Networks) -> anyhow::Result<()> {
+    for params in &scenario.params {
+        let folder_name = run_test(scenario, params, known_networks).await?;
+
+        // Keep the scenario together with the data it produced
+        if let Some(source) = &scenario.source {
+            if let Some(file_name) = source.file_name() {
+                fs::copy(source, Path::new(&folder_name).join(file_name))?;
+            }
+        }
+    }
+    Ok(())
 }
 
 pub async fn run_test(
-    networks: &Vec<IotaTangleNetwork>,
-    num_threads: usize,
-    iterations: usize,
-) -> anyhow::Result<()> {
-    let mut all_measurements: HashMap<IotaTangleNetwork, Measurement> = HashMap::new();
+    scenario: &Scenario,
+    params: &Params,
+    known_networks: &Networks,
+) -> anyhow::Result<String> {
+    let mut all_measurements: HashMap<String, Measurement> = HashMap::new();
+    let mut all_node_usage: HashMap<String, HashMap<String, NodeUsage>> = HashMap::new();
+
+    // Reading from the node that was written to would not show how a change propagates
+    if scenario.consistency.as_ref().is_some_and(|c| c.cross_node) {
+        if let Some(network) = scenario.networks.iter().find(|n| n.api_endpoints.len() < 2) {
+            anyhow::bail!(
+                "the cross-node consistency check needs at least two API endpoints, {} has {}",
+                network.name(),
+                network.api_endpoints.len()
+            );
+        }
+    }
 
-    for network in networks {
-        let measurements = all_measurements
-            .entry(*network)
-            .or_insert_with(Measurement::new);
-        spawn_tasks(measurements, num_threads, iterations, *network).await?;
+    let setups = scenario.key_storage.setups();
+    for network in &scenario.networks {
+        for (setup, label) in &setups {
+            // With several key setups every setup gets its own measurement, e.g. `localhost [memory]`
+            let measurement_id = measurement_id(&network.id, label);
+            let measurements = all_measurements
+                .entry(measurement_id.clone())
+                .or_insert_with(Measurement::new);
+            let node_usage = all_node_usage
+                .entry(measurement_id)
+                .or_insert_with(HashMap::new);
+            spawn_tasks(measurements, node_usage, params, scenario, network, *setup).await?;
+        }
     }
 
     // let pretty_json = serde_json::to_string_pretty(&all_measurements).unwrap();
     // info!("Result: {} \n", pretty_json);
 
     // Print results
-    println!("Num threads: {}", num_threads);
-    println!("Iterations: {}", iterations);
-    for (network, measurement) in &all_measurements {
-        println!("Test results for {}", network.name());
+    println!("Num threads: {}", params.num_threads);
+    println!("Iterations: {}", params.iterations);
+    for (network_id, measurement) in &all_measurements {
+        println!("Test results for {}", known_networks.name_of(network_id));
         print_measurement_stats(measurement);
+        if let Some(node_usage) = all_node_usage.get(network_id) {
+            print_node_usage(node_usage);
+        }
+    }
+    if setups.len() > 1 {
+        for network in &scenario.networks {
+            println!("Key setups on {}", network.name());
+            let runs: Vec<(String, &Measurement)> = setups
+                .iter()
+                .filter_map(|(_, label)| {
+                    all_measurements
+                        .get(&measurement_id(&network.id, label))
+                        .map(|measurement| (label.clone().unwrap_or_default(), measurement))
+                })
+                .collect();
+            print_run_comparison(&runs);
+        }
     }
 
-    let folder_name = get_and_create_folder().unwrap();
+    let folder_name = get_and_create_folder_in(&scenario.output_dir)?;
     let json_data = to_string_pretty(&all_measurements).unwrap();
     save_to_raw_data_file(json_data, &folder_name)?;
+    let json_data = to_string_pretty(&all_node_usage).unwrap();
+    save_to_file(json_data, &folder_name, "node_usage")?;
 
-    if let Err(e) = draw_all_measurements(&folder_name, &all_measurements) {
+    if let Err(e) = draw_all_measurements(&folder_name, &all_measurements, known_networks) {
         warn!("Failed generate images: {:?}", e);
     }
-    Ok(())
+    Ok(folder_name)
 }
 
 // pub async fn test_localhost() -> anyhow::Result<()> {
@@ -125,61 +293,155 @@ pub async fn run_test(
 //     Ok(())
 // }
 
+/// Key of the measurements of `network`, with the label of the key setup if there are several.
+fn measurement_id(network_id: &str, label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!("{} [{}]", network_id, label),
+        None => network_id.to_string(),
+    }
+}
+
 async fn spawn_tasks(
     measurements: &mut Measurement,
-    num_threads: usize,
-    iterations: usize,
-    network: IotaTangleNetwork,
+    node_usage: &mut HashMap<String, NodeUsage>,
+    params: &Params,
+    scenario: &Scenario,
+    network: &IotaTangleNetwork,
+    setup: KeySetup,
 ) -> anyhow::Result<()> {
     let mut handles = vec![];
+    let node_strategy = scenario.node_strategy;
+    let retry_policy = &scenario.retry_policy;
+    let confirmation = scenario.confirmation;
+    let track_cost = scenario.track_cost;
+    let consistency = scenario.consistency.clone();
+    let key_storage = &scenario.key_storage;
+    let state_dir = scenario
+        .state_dir
+        .as_ref()
+        .map(|state_dir| state_dir.join(&network.id));
 
     info!(
-        "Starting testing for {}\nAPI: {}\nFaucet: {}",
+        "Starting testing for {}\nAPI: {}\nFaucet: {}\nNode strategy: {:?}\nKey storage: {} ({})",
         network.name(),
-        network.api_endpoint(),
-        network.faucet_endpoint()
+        network.api_endpoints.join(", "),
+        network.faucet_endpoint(),
+        node_strategy,
+        setup.backend.name(),
+        setup.algorithm.name()
     );
+    if confirmation {
+        info!("Timing every action until its milestone inclusion and resolution");
+    }
+    if let Some(consistency) = &consistency {
+        info!(
+            "Checking the consistency after every change: {:?}",
+            consistency
+        );
+    }
+    if retry_policy.max_attempts > 1 {
+        info!("Retry policy: {:?}", retry_policy);
+    }
 
-    for _ in 0..num_threads {
+    for task_id in 0..params.num_threads {
         let network = network.clone();
-        let iterations = iterations.clone();
+        let params = params.clone();
+        let actions = scenario.actions.clone();
+        let retry_policy = retry_policy.clone();
+        let consistency = consistency.clone();
+        let key_storage = key_storage.clone();
+        let state_file = state_dir
+            .as_ref()
+            .map(|state_dir| state_dir.join(format!("task_{}.json", task_id)));
 
         let handle = task::spawn(async move {
             let mut measurement = Measurement::new();
-
-            match DIDManager::new(network.api_endpoint(), network.faucet_endpoint()).await {
-                Ok(mut did_manager) => {
-                    let actions = vec![
-                        Action::CreateDid,
-                        Action::UpdateDid,
-                        // Action::ResolveDid,
-                        // Action::DeactivateDid,
-                        // Action::ReactivateDid,
-                        // Action::DeleteDid,
-                    ];
-
-                    for action in &actions {
+            let mut node_usage = HashMap::new();
+
+            let did_manager = match &state_file {
+                Some(state_file) => {
+                    DIDManager::open(
+                        &network,
+                        node_strategy,
+                        &key_storage,
+                        setup.backend,
+                        state_file,
+                    )
+                    .await
+                }
+                None => DIDManager::new(&network, node_strategy, &key_storage, setup.backend).await,
+            };
+            match did_manager {
+                Ok(did_manager) => {
+                    let mut did_manager = did_manager
+                        .with_retry_policy(retry_policy)
+                        .with_confirmation(confirmation)
+                        .with_consistency_check(consistency)
+                        .with_key_algorithm(setup.algorithm);
+                    for scenario_action in &actions {
+                        let action = &scenario_action.action;
                         let action_measurements =
                             measurement.entry(*action).or_insert_with(Vec::new);
 
-                        for index in 0..iterations {
+                        // The balance queries are not part of the duration
+                        let track_cost = track_cost && *action != Action::ResolveDid;
+                        for index in 0..scenario_action.iterations(&params) {
+                            let balance_before = if track_cost {
+                                did_manager.wallet_balance().await
+                            } else {
+                                None
+                            };
+                            let start_time = Utc::now();
                             let start = Instant::now();
 
-                            did_manager.run_action(action, index).await;
-
-                            let duration = start.elapsed();
-                            action_measurements.push(duration.as_secs_f64());
+                            let result = did_manager.run_action(action, index).await;
+
+                            // Retries are recorded apart from the duration of the last attempt
+                            let retries = did_manager.retries();
+                            let duration = start.elapsed().saturating_sub(retries.time);
+                            // Saving the DIDs is not part of the duration either
+                            if action.changes_dids() {
+                                if let Err(e) = did_manager.save_state() {
+                                    warn!("Failed to save the DIDs: {:?}", e);
+                                }
+                            }
+                            let balance_after = if track_cost {
+                                did_manager.wallet_balance().await
+                            } else {
+                                None
+                            };
+                            let sample = Sample::new(start_time, duration, task_id, index)
+                                .with_node(did_manager.current_node())
+                                .with_retries(retries.count, retries.time)
+                                .with_phases(did_manager.phases())
+                                .with_output_size(did_manager.output_size())
+                                .with_token_cost(
+                                    did_manager.token_cost(balance_before, balance_after),
+                                );
+                            let sample = match result {
+                                Ok(()) => sample.with_consistency(
+                                    did_manager.check_consistency(action, index).await,
+                                ),
+                                Err(kind) => sample.failed(kind),
+                            };
+                            action_measurements.push(sample);
                         }
 
                         // sleep(Duration::from_millis(5000)).await; // Wait 500 milliseconds before starting each thread
                     }
+
+                    node_usage = did_manager.node_usage();
                 }
                 Err(e) => {
-                    warn!("Failed to create DIDManager: {:?}", e);
+                    warn!(
+                        "Failed to create DIDManager ({}): {:?}",
+                        FailureKind::classify(&e),
+                        e
+                    );
                 }
             }
 
-            measurement
+            (measurement, node_usage)
         });
 
         handles.push(handle);
@@ -188,11 +450,12 @@ async fn spawn_tasks(
     // Await all the tasks to complete
     for handle in handles {
         match handle.await {
-            Ok(mut result) => {
+            Ok((mut result, task_node_usage)) => {
                 for (action, durations) in &mut result {
                     let element = measurements.entry(*action).or_insert_with(Vec::new);
                     element.append(durations);
                 }
+                merge_node_usage(node_usage, &task_node_usage);
             }
             Err(err) => {
                 warn!("Invalid thread results: {:?}", err);
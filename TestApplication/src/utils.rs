@@ -1,7 +1,15 @@
 // Copyright 2020-2023 IOTA Stiftung
 // SPDX-License-Identifier: Apache-2.0
 
+use crate::consistency::Consistency;
+use crate::failure::{FailureKind, FaucetTimeout};
+use crate::statistics::{
+    bootstrap_ci, calculate_stats, mann_whitney_u, mean_of, percentile, welch_t_test, Stats,
+    TestResult,
+};
 use anyhow::Context;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use iota_sdk::client::api::GetAddressesOptions;
 use iota_sdk::client::node_api::indexer::query_parameters::QueryParameter;
 use iota_sdk::client::secret::SecretManager;
@@ -14,85 +22,252 @@ use log::info;
 use rand::distributions::DistString;
 use serde::Deserialize;
 use serde::Serialize;
-use statrs::statistics::Statistics;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs::read_to_string;
 use std::fs::File;
 use std::io::{self, Write};
-use std::path::PathBuf;
-use strum::EnumIter;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use strum::{EnumIter, IntoEnumIterator};
 
-pub type Measurement = HashMap<Action, Vec<f64>>;
-pub struct MeasurementResult {
-    pub measurement: Measurement,
-    pub failures: usize,
+pub type Measurement = HashMap<Action, Vec<Sample>>;
+
+/// Coverage of the printed confidence intervals.
+const CONFIDENCE: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Outcome {
+    Success,
+    Failure,
 }
 
-impl MeasurementResult {
-    pub fn new() -> Self {
+/// One timed execution of an action.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    /// Wall-clock time the first attempt started. `None` for samples migrated from old raw data files.
+    pub start: Option<DateTime<Utc>>,
+    /// Duration in seconds of the last attempt. With retries the action ends
+    /// `retry_time + duration` seconds after `start`.
+    pub duration: f64,
+    pub task: Option<usize>,
+    /// Iteration of the task. For DID actions this is also the index of the DID in the `DIDManager`.
+    pub iteration: Option<usize>,
+    /// URL of the node that served the action.
+    pub node: Option<String>,
+    pub outcome: Outcome,
+    pub error_kind: Option<FailureKind>,
+    /// Attempts before the last one that failed and were retried.
+    #[serde(default)]
+    pub retries: u32,
+    /// Time in seconds spent in the retried attempts and the backoff, not included in `duration`.
+    #[serde(default)]
+    pub retry_time: f64,
+    /// Duration in seconds of each phase of the last attempt, if the phases were timed.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub phases: BTreeMap<Phase, f64>,
+    /// Read-after-write check of the changed DID, not included in `duration`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consistency: Option<Consistency>,
+    /// Alias Output published by the action, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_size: Option<OutputSize>,
+    /// Tokens the action locked in or reclaimed from the Alias Output, if it changed one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_cost: Option<TokenCost>,
+}
+
+impl Sample {
+    pub fn new(start: DateTime<Utc>, duration: Duration, task: usize, iteration: usize) -> Self {
         Self {
-            measurement: Measurement::new(),
-            failures: 0,
+            start: Some(start),
+            duration: duration.as_secs_f64(),
+            task: Some(task),
+            iteration: Some(iteration),
+            node: None,
+            outcome: Outcome::Success,
+            error_kind: None,
+            retries: 0,
+            retry_time: 0.0,
+            phases: BTreeMap::new(),
+            consistency: None,
+            output_size: None,
+            token_cost: None,
         }
     }
+
+    pub fn with_node(mut self, node: &str) -> Self {
+        self.node = Some(node.to_string());
+        self
+    }
+
+    pub fn with_retries(mut self, retries: u32, retry_time: Duration) -> Self {
+        self.retries = retries;
+        self.retry_time = retry_time.as_secs_f64();
+        self
+    }
+
+    pub fn with_phases(mut self, phases: BTreeMap<Phase, f64>) -> Self {
+        self.phases = phases;
+        self
+    }
+
+    pub fn with_output_size(mut self, output_size: Option<OutputSize>) -> Self {
+        self.output_size = output_size;
+        self
+    }
+
+    pub fn with_token_cost(mut self, token_cost: Option<TokenCost>) -> Self {
+        self.token_cost = token_cost;
+        self
+    }
+
+    pub fn with_consistency(mut self, consistency: Option<Consistency>) -> Self {
+        self.consistency = consistency;
+        self
+    }
+
+    pub fn failed(mut self, error_kind: FailureKind) -> Self {
+        self.outcome = Outcome::Failure;
+        self.error_kind = Some(error_kind);
+        self
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum IotaTangleNetwork {
-    Localhost,
-    Localhost2,
-    IotaTestnet,
-    ShimmerTestnet,
-    IotaTestnet2_0,
+/// A sample as stored in a raw data file. Old files only contain the duration.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SampleRecord {
+    Sample(Box<Sample>),
+    Duration(f64),
 }
 
-impl IotaTangleNetwork {
-    pub fn name(&self) -> &'static str {
-        match self {
-            IotaTangleNetwork::Localhost => "Localhost",
-            IotaTangleNetwork::Localhost2 => "Localhost 2",
-            IotaTangleNetwork::IotaTestnet => "Iota testnet",
-            IotaTangleNetwork::ShimmerTestnet => "Shimmer testnet",
-            IotaTangleNetwork::IotaTestnet2_0 => "IOTA 2.0 testnet",
+impl From<SampleRecord> for Sample {
+    fn from(record: SampleRecord) -> Self {
+        match record {
+            SampleRecord::Sample(sample) => *sample,
+            SampleRecord::Duration(duration) => Sample {
+                start: None,
+                duration,
+                task: None,
+                iteration: None,
+                ..Sample::new(Utc::now(), Duration::ZERO, 0, 0)
+            },
         }
     }
+}
 
-    pub fn api_endpoint(&self) -> &'static str {
-        match self {
-            IotaTangleNetwork::Localhost => "http://localhost:14265",
-            IotaTangleNetwork::Localhost2 => "http://localhost:14266",
-            IotaTangleNetwork::IotaTestnet => "https://api.testnet.iotaledger.net",
-            IotaTangleNetwork::ShimmerTestnet => "https://api.testnet.shimmer.network",
-            IotaTangleNetwork::IotaTestnet2_0 => "https://api.nova-testnet.iotaledger.net/",
+/// Key of the block test measurement, whose raw data file is not split by network.
+pub const BLOCK_TEST_KEY: &str = "Blocks";
+
+/// Parses the content of a raw data file, keyed by network id.
+/// Files written before samples were recorded, with plain durations, are converted to samples.
+/// The raw data of the block test is returned under [`BLOCK_TEST_KEY`].
+pub fn parse_raw_data(json_data: &str) -> anyhow::Result<HashMap<String, Measurement>> {
+    let records: HashMap<String, HashMap<Action, Vec<SampleRecord>>> =
+        match serde_json::from_str(json_data) {
+            Ok(records) => records,
+            Err(e) => match serde_json::from_str(json_data) {
+                Ok(block_records) => HashMap::from([(BLOCK_TEST_KEY.to_string(), block_records)]),
+                Err(_) => return Err(e.into()),
+            },
+        };
+
+    Ok(records
+        .into_iter()
+        .map(|(network_id, measurement)| {
+            let measurement = measurement
+                .into_iter()
+                .map(|(action, records)| (action, records.into_iter().map(Sample::from).collect()))
+                .collect();
+            (network_id, measurement)
+        })
+        .collect())
+}
+
+/// Loads a raw data file, or the `raw_data` file of a results folder.
+pub fn load_raw_data(path: &str) -> anyhow::Result<HashMap<String, Measurement>> {
+    let json_data = if Path::new(path).is_dir() {
+        load_from_file(path)
+    } else {
+        read_to_string(path).map_err(anyhow::Error::from)
+    }
+    .with_context(|| format!("failed to read raw data {}", path))?;
+
+    parse_raw_data(&json_data).with_context(|| format!("invalid raw data {}", path))
+}
+
+/// Durations in seconds of the successful samples. Failed actions are left out
+/// so they do not distort the latency distribution.
+pub fn durations(samples: &[Sample]) -> Vec<f64> {
+    samples
+        .iter()
+        .filter(|sample| sample.outcome == Outcome::Success)
+        .map(|sample| sample.duration)
+        .collect()
+}
+
+/// Durations in seconds of `phase` in the successful samples that timed it.
+pub fn phase_durations(samples: &[Sample], phase: Phase) -> Vec<f64> {
+    samples
+        .iter()
+        .filter(|sample| sample.outcome == Outcome::Success)
+        .filter_map(|sample| sample.phases.get(&phase).copied())
+        .collect()
+}
+
+/// Number of failed samples per failure kind.
+pub fn failures_by_kind(samples: &[Sample]) -> HashMap<FailureKind, usize> {
+    let mut failures = HashMap::new();
+    for sample in samples {
+        if sample.outcome == Outcome::Failure {
+            *failures
+                .entry(sample.error_kind.unwrap_or(FailureKind::Other))
+                .or_insert(0) += 1;
         }
     }
+    failures
+}
 
-    pub fn faucet_endpoint(&self) -> &'static str {
-        match self {
-            IotaTangleNetwork::Localhost => "http://localhost:8091/api/enqueue",
-            IotaTangleNetwork::Localhost2 => "http://localhost:8091/api/enqueue",
-            IotaTangleNetwork::IotaTestnet => "https://faucet.testnet.iotaledger.net/api/enqueue",
-            IotaTangleNetwork::ShimmerTestnet => {
-                "https://faucet.testnet.shimmer.network/api/enqueue"
-            }
-            IotaTangleNetwork::IotaTestnet2_0 => {
-                "https://faucet.nova-testnet.iotaledger.net/api/enqueue"
-            }
+pub struct MeasurementResult {
+    pub measurement: Measurement,
+    pub failures: usize,
+}
+
+impl MeasurementResult {
+    pub fn new() -> Self {
+        Self {
+            measurement: Measurement::new(),
+            failures: 0,
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, EnumIter, ValueEnum)]
 pub enum Action {
     CreateDid,
     DeleteDid,
     UpdateDid,
     DeactivateDid,
     ReactivateDid,
+    AddService,
+    UpdateService,
+    RemoveService,
+    IssueCredential,
+    VerifyCredential,
+    CreatePresentation,
+    VerifyPresentation,
+    AddRevocationBitmap,
+    RevokeCredential,
+    UnrevokeCredential,
+    CheckCredentialStatus,
     ResolveDid,
+    #[value(skip)]
     CreateAndPostBlock,
+    #[value(skip)]
     nodes_2,
+    #[value(skip)]
     nodes_3,
+    #[value(skip)]
     nodes_4,
 }
 
@@ -104,6 +279,17 @@ impl Action {
             Action::UpdateDid => "Update DID",
             Action::DeactivateDid => "Deactivate DID",
             Action::ReactivateDid => "Reactivate DID",
+            Action::AddService => "Add service",
+            Action::UpdateService => "Update service",
+            Action::RemoveService => "Remove service",
+            Action::IssueCredential => "Issue credential",
+            Action::VerifyCredential => "Verify credential",
+            Action::CreatePresentation => "Create presentation",
+            Action::VerifyPresentation => "Verify presentation",
+            Action::AddRevocationBitmap => "Add revocation bitmap",
+            Action::RevokeCredential => "Revoke credential",
+            Action::UnrevokeCredential => "Unrevoke credential",
+            Action::CheckCredentialStatus => "Check credential status",
             Action::ResolveDid => "Resolve DID",
             Action::CreateAndPostBlock => "Create and Post Block",
             Action::nodes_2 => "2 Nodes",
@@ -111,9 +297,111 @@ impl Action {
             Action::nodes_4 => "4 Nodes",
         }
     }
+
+    /// Whether the action changes the DIDs that are kept in the state file.
+    pub fn changes_dids(&self) -> bool {
+        !matches!(
+            self,
+            Action::VerifyCredential
+                | Action::VerifyPresentation
+                | Action::CheckCredentialStatus
+                | Action::ResolveDid
+                | Action::CreateAndPostBlock
+                | Action::nodes_2
+                | Action::nodes_3
+                | Action::nodes_4
+        )
+    }
+
+    /// Number of running nodes of a block test configuration.
+    pub fn node_count(&self) -> Option<usize> {
+        match self {
+            Action::nodes_2 => Some(2),
+            Action::nodes_3 => Some(3),
+            Action::nodes_4 => Some(4),
+            _ => None,
+        }
+    }
+}
+
+/// Size of an Alias Output holding a DID document.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct OutputSize {
+    /// Length in bytes of the packed DID document in the state metadata.
+    pub document_size: usize,
+    /// Amount of the output in base tokens, the minimum storage deposit for its size.
+    pub storage_deposit: u64,
+    /// Services of the DID document.
+    #[serde(default)]
+    pub services: usize,
+    /// Revoked credential indices in the revocation bitmap of the document.
+    #[serde(default)]
+    pub revoked: u64,
+}
+
+/// Tokens an action locked in or reclaimed from the Alias Output of a DID. There are no fees,
+/// so the storage deposit is the whole token cost.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TokenCost {
+    /// Amount of the Alias Output before the action, 0 for a new DID.
+    pub amount_before: u64,
+    /// Amount of the Alias Output after the action, 0 for a deleted DID.
+    pub amount_after: u64,
+    /// Balance of the wallet before the action, if it was tracked.
+    pub balance_before: Option<u64>,
+    /// Balance of the wallet after the action, if it was tracked.
+    pub balance_after: Option<u64>,
+}
+
+impl TokenCost {
+    /// Tokens locked in the Alias Output by the action, negative if they were reclaimed.
+    pub fn deposit_change(&self) -> i64 {
+        self.amount_after as i64 - self.amount_before as i64
+    }
+
+    /// Change of the wallet balance, if it was tracked.
+    pub fn balance_change(&self) -> Option<i64> {
+        Some(self.balance_after? as i64 - self.balance_before? as i64)
+    }
+}
+
+/// Part of an action that is timed on its own.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, EnumIter,
+)]
+pub enum Phase {
+    /// Building, signing and posting the block until the node accepted it.
+    Submit,
+    /// From the accepted block until a milestone included it.
+    Inclusion,
+    /// From the inclusion until the resolver returns the new state.
+    Resolution,
+    /// Requesting the parents of a block from the node.
+    TipSelection,
+    /// Local proof of work. With remote PoW the node does it as part of [`Phase::Post`].
+    Pow,
+    /// Posting the block until the node returned its id.
+    Post,
+    /// Generating and deleting the keys of verification methods, including the stronghold snapshot I/O.
+    KeyStorage,
+}
+
+impl Phase {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Phase::Submit => "Submit",
+            Phase::Inclusion => "Inclusion",
+            Phase::Resolution => "Resolution",
+            Phase::TipSelection => "Tip selection",
+            Phase::Pow => "PoW",
+            Phase::Post => "Post",
+            Phase::KeyStorage => "Key storage",
+        }
+    }
 }
 
 /// Generates an address from the given [`SecretManager`] and adds funds from the faucet.
+/// An address that already holds funds, e.g. of a reused stronghold, is not funded again.
 pub async fn get_address_with_funds(
     client: &Client,
     stronghold: &SecretManager,
@@ -121,6 +409,10 @@ pub async fn get_address_with_funds(
 ) -> anyhow::Result<Address> {
     let address: Bech32Address = get_address(client, stronghold).await?;
 
+    if get_address_balance(client, &address).await? > 0 {
+        return Ok(*address);
+    }
+
     request_faucet_funds(client, address, faucet_endpoint)
         .await
         .context("failed to request faucet funds")?;
@@ -128,24 +420,29 @@ pub async fn get_address_with_funds(
     Ok(*address)
 }
 
+pub fn random_mnemonic() -> anyhow::Result<bip39::Mnemonic> {
+    let random: [u8; 32] = rand::random();
+    bip39::wordlist::encode(random.as_ref(), &bip39::wordlist::ENGLISH)
+        .map_err(|err| anyhow::anyhow!(format!("{err:?}")))
+}
+
 /// Initializes the [`SecretManager`] with a new mnemonic, if necessary,
 /// and generates an address from the given [`SecretManager`].
 pub async fn get_address(
     client: &Client,
     secret_manager: &SecretManager,
 ) -> anyhow::Result<Bech32Address> {
-    let random: [u8; 32] = rand::random();
-    let mnemonic = bip39::wordlist::encode(random.as_ref(), &bip39::wordlist::ENGLISH)
-        .map_err(|err| anyhow::anyhow!(format!("{err:?}")))?;
-
-    if let SecretManager::Stronghold(ref stronghold) = secret_manager {
-        match stronghold.store_mnemonic(mnemonic).await {
-            Ok(()) => (),
-            Err(iota_sdk::client::stronghold::Error::MnemonicAlreadyStored) => (),
-            Err(err) => anyhow::bail!(err),
+    match secret_manager {
+        SecretManager::Stronghold(stronghold) => {
+            match stronghold.store_mnemonic(random_mnemonic()?).await {
+                Ok(()) => (),
+                Err(iota_sdk::client::stronghold::Error::MnemonicAlreadyStored) => (),
+                Err(err) => anyhow::bail!(err),
+            }
         }
-    } else {
-        anyhow::bail!("expected a `StrongholdSecretManager`");
+        // Created from a mnemonic already
+        SecretManager::Mnemonic(_) => (),
+        _ => anyhow::bail!("expected a `StrongholdSecretManager` or a `MnemonicSecretManager`"),
     }
 
     let bech32_hrp: Hrp = client.get_bech32_hrp().await?;
@@ -182,13 +479,13 @@ async fn request_faucet_funds(
         Ok::<(), anyhow::Error>(())
     })
     .await
-    .context("maximum timeout exceeded")??;
+    .map_err(|_| FaucetTimeout(address.to_string()))??;
 
     Ok(())
 }
 
 /// Returns the balance of the given Bech32-encoded `address`.
-async fn get_address_balance(client: &Client, address: &Bech32Address) -> anyhow::Result<u64> {
+pub async fn get_address_balance(client: &Client, address: &Bech32Address) -> anyhow::Result<u64> {
     let output_ids = client
         .basic_output_ids(vec![
             QueryParameter::Address(address.to_owned()),
@@ -217,63 +514,591 @@ pub fn random_stronghold_path() -> PathBuf {
     file.to_owned()
 }
 
-pub fn utf8_to_hex(utf8_data: &str) -> String {
-    // Convert the UTF-8 string to bytes and then format as hex
-    let hex_string: String = utf8_data
-        .as_bytes() // Get the UTF-8 byte representation
-        .iter() // Iterate over the bytes
-        .map(|byte| format!("{:02x}", byte)) // Format each byte as a two-digit hex value
-        .collect(); // Collect into a single string
+pub fn print_measurement_stats(measurement: &Measurement) {
+    print_stats_header("Action");
+    for (action, samples) in measurement {
+        let stats = calculate_stats(&durations(samples));
+        print_stats_row(action.name(), samples.len() - stats.count, &stats);
+    }
+
+    print_phase_stats(measurement);
+    print_phase_shares(measurement);
+    print_failures(measurement);
+    print_retries(measurement);
+    print_consistency(measurement);
+    print_output_sizes(measurement);
+    print_latency_by_services(measurement);
+    print_token_costs(measurement);
+    print_credential_flow(measurement);
+    print_revocation_sizes(measurement);
+}
+
+pub fn print_stats_header(label: &str) {
+    println!(
+        "{0: <15} | {1: <8} | {2: <8} | {3: <8} | {4: <8} | {5: <8} | {6: <8} | {7: <8} | {8: <8} | {9: <8} | {10: <8} | {11: <8} | {12: <8}",
+        label, "Samples", "Failures", "Min", "Max", "Mean", "Std dev", "MAD", "p50", "p90", "p95", "p99", "p99.9"
+    );
+}
 
-    format!("0x{}", hex_string) // Prepend with "0x" for hex notation
+pub fn print_stats_row(label: &str, failures: usize, stats: &Stats) {
+    println!(
+        "{0: <15} | {1: <8} | {2: <8} | {3: <8.4} | {4: <8.4} | {5: <8.4} | {6: <8.4} | {7: <8.4} | {8: <8.4} | {9: <8.4} | {10: <8.4} | {11: <8.4} | {12: <8.4}",
+        label,
+        stats.count + failures,
+        failures,
+        stats.min,
+        stats.max,
+        stats.mean,
+        stats.std_dev,
+        stats.mad,
+        stats.p50,
+        stats.p90,
+        stats.p95,
+        stats.p99,
+        stats.p999,
+    );
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Default)]
-pub struct Stats {
-    pub min: f64,
-    pub max: f64,
-    pub mean: f64,
-    pub variance: f64,
+/// Prints the statistics of every timed phase of the successful samples.
+/// Prints nothing if no phases were timed.
+pub fn print_phase_stats(measurement: &Measurement) {
+    let mut rows = vec![];
+    for (action, samples) in measurement {
+        for phase in Phase::iter() {
+            let durations = phase_durations(samples, phase);
+            if !durations.is_empty() {
+                rows.push((
+                    format!("{} {}", action.name(), phase.name()),
+                    calculate_stats(&durations),
+                ));
+            }
+        }
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    print_stats_header("Phase");
+    for (label, stats) in &rows {
+        print_stats_row(label, 0, stats);
+    }
 }
 
-pub fn calculate_stats(numbers: &Vec<f64>) -> Stats {
-    if numbers.is_empty() {
-        // Return default values if the vector is empty
-        return Stats::default();
+/// Prints the share of every timed phase in the mean duration of the successful samples,
+/// e.g. how much of creating a DID is spent in the key storage rather than on the ledger.
+/// Prints nothing if no phases were timed.
+pub fn print_phase_shares(measurement: &Measurement) {
+    let mut rows = vec![];
+    for (action, samples) in measurement {
+        let total = mean_of(&durations(samples));
+        for phase in Phase::iter() {
+            let phase_durations = phase_durations(samples, phase);
+            if !phase_durations.is_empty() {
+                let mean = mean_of(&phase_durations);
+                rows.push((action.name(), phase.name(), mean, mean / total * 100.0));
+            }
+        }
     }
-    Stats {
-        min: numbers.clone().min(),
-        max: numbers.clone().max(),
-        mean: numbers.clone().mean(),
-        variance: numbers.clone().variance(),
+    if rows.is_empty() {
+        return;
+    }
+
+    println!(
+        "{0: <15} | {1: <15} | {2: <10} | {3: <10}",
+        "Action", "Phase", "Mean", "Share"
+    );
+    for (action, phase, mean, share) in rows {
+        println!(
+            "{0: <15} | {1: <15} | {2: <10.4} | {3: <9.1}%",
+            action, phase, mean, share
+        );
     }
 }
 
-pub fn print_measurement_stats(measurement: &Measurement) {
+/// Prints 95% bootstrap confidence intervals of the mean and the main percentiles of every action.
+pub fn print_confidence_intervals(measurement: &Measurement) {
     println!(
-        "{0: <15} | {1: <10} | {2: <10} | {3: <10} | {4: <10}",
-        "Action", "Min", "Max", "Mean", "Variance"
+        "{0: <15} | {1: <19} | {2: <19} | {3: <19} | {4: <19}",
+        "Action", "Mean 95% CI", "p50 95% CI", "p95 95% CI", "p99 95% CI"
     );
-    for (action, durations) in measurement {
-        if durations.clone().variance() < 0.0001 {
+    for (action, samples) in measurement {
+        let durations = durations(samples);
+        let intervals = [
+            bootstrap_ci(&durations, mean_of, CONFIDENCE),
+            bootstrap_ci(&durations, |x| percentile(x, 0.5), CONFIDENCE),
+            bootstrap_ci(&durations, |x| percentile(x, 0.95), CONFIDENCE),
+            bootstrap_ci(&durations, |x| percentile(x, 0.99), CONFIDENCE),
+        ]
+        .map(|ci| format!("[{:.4}, {:.4}]", ci.lower, ci.upper));
+        println!(
+            "{0: <15} | {1: <19} | {2: <19} | {3: <19} | {4: <19}",
+            action.name(),
+            intervals[0],
+            intervals[1],
+            intervals[2],
+            intervals[3],
+        );
+    }
+}
+
+/// Prints the difference of the mean latency between two measurements per action and whether
+/// it is significant at level `alpha`, according to both Welch's t-test and the Mann–Whitney U test.
+pub fn print_comparison(baseline: &Measurement, candidate: &Measurement, alpha: f64) {
+    println!(
+        "{0: <15} | {1: <19} | {2: <19} | {3: <10} | {4: <10} | {5: <10} | {6: <11}",
+        "Action",
+        "Baseline mean CI",
+        "Candidate mean CI",
+        "Change",
+        "Welch p",
+        "MWU p",
+        "Significant"
+    );
+    for action in Action::iter() {
+        let (Some(a), Some(b)) = (baseline.get(&action), candidate.get(&action)) else {
+            continue;
+        };
+        let (a, b) = (durations(a), durations(b));
+        let ci_a = bootstrap_ci(&a, mean_of, CONFIDENCE);
+        let ci_b = bootstrap_ci(&b, mean_of, CONFIDENCE);
+        let change = (mean_of(&b) - mean_of(&a)) / mean_of(&a) * 100.0;
+        let welch = welch_t_test(&a, &b);
+        let mwu = mann_whitney_u(&a, &b);
+        let significant = match (welch, mwu) {
+            (Some(welch), Some(mwu)) => welch.p_value < alpha && mwu.p_value < alpha,
+            _ => false,
+        };
+        println!(
+            "{0: <15} | {1: <19} | {2: <19} | {3: <+9.1}% | {4: <10} | {5: <10} | {6: <11}",
+            action.name(),
+            format!("[{:.4}, {:.4}]", ci_a.lower, ci_a.upper),
+            format!("[{:.4}, {:.4}]", ci_b.lower, ci_b.upper),
+            change,
+            format_p_value(welch),
+            format_p_value(mwu),
+            if significant { "yes" } else { "no" },
+        );
+    }
+}
+
+fn format_p_value(result: Option<TestResult>) -> String {
+    match result {
+        Some(result) => format!("{:.4e}", result.p_value),
+        None => "-".to_string(),
+    }
+}
+
+/// Prints the statistics of several runs per action, with the change relative to the first run.
+pub fn print_run_comparison(runs: &[(String, &Measurement)]) {
+    for action in Action::iter() {
+        let stats: Vec<(&str, usize, Stats)> = runs
+            .iter()
+            .filter_map(|(label, measurement)| {
+                measurement.get(&action).map(|samples| {
+                    let stats = calculate_stats(&durations(samples));
+                    (label.as_str(), samples.len() - stats.count, stats)
+                })
+            })
+            .collect();
+        let Some((_, _, reference)) = stats.first() else {
+            continue;
+        };
+        let reference = *reference;
+
+        println!("{}", action.name());
+        println!(
+            "{0: <40} | {1: <8} | {2: <8} | {3: <8} | {4: <8} | {5: <8} | {6: <8} | {7: <8} | {8: <8} | {9: <8} | {10: <8}",
+            "Run", "Samples", "Failures", "Mean", "Change", "p50", "Change", "p95", "Change", "p99", "Change"
+        );
+        for (label, failures, stats) in &stats {
             println!(
-                "{0: <15} | {1: <10.4} | {2: <10.4} | {3: <10.4} | {4: <10.4e}",
-                action.name(),
-                durations.clone().min(),
-                durations.clone().max(),
-                durations.clone().mean(),
-                durations.clone().variance(),
+                "{0: <40} | {1: <8} | {2: <8} | {3: <8.4} | {4: <8} | {5: <8.4} | {6: <8} | {7: <8.4} | {8: <8} | {9: <8.4} | {10: <8}",
+                label,
+                stats.count + failures,
+                failures,
+                stats.mean,
+                relative_change(reference.mean, stats.mean),
+                stats.p50,
+                relative_change(reference.p50, stats.p50),
+                stats.p95,
+                relative_change(reference.p95, stats.p95),
+                stats.p99,
+                relative_change(reference.p99, stats.p99),
             );
-        } else {
-            println!(
-                "{0: <15} | {1: <10.4} | {2: <10.4} | {3: <10.4} | {4: <10.4}",
+        }
+    }
+}
+
+fn relative_change(reference: f64, value: f64) -> String {
+    if reference == 0.0 {
+        "-".to_string()
+    } else {
+        format!("{:+.1}%", (value - reference) / reference * 100.0)
+    }
+}
+
+/// Prints the failed actions grouped by failure kind. Prints nothing if every action succeeded.
+pub fn print_failures(measurement: &Measurement) {
+    let mut rows = vec![];
+    for (action, samples) in measurement {
+        let failures = failures_by_kind(samples);
+        for kind in FailureKind::iter() {
+            if let Some(count) = failures.get(&kind) {
+                rows.push((action.name(), kind, *count));
+            }
+        }
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    println!(
+        "{0: <15} | {1: <20} | {2: <10}",
+        "Action", "Failure", "Count"
+    );
+    for (action, kind, count) in rows {
+        println!("{0: <15} | {1: <20} | {2: <10}", action, kind.name(), count);
+    }
+}
+
+/// Prints how many actions were retried, how many of them succeeded in the end and the time
+/// spent in the retries. Prints nothing if no action was retried.
+pub fn print_retries(measurement: &Measurement) {
+    let mut rows = vec![];
+    for (action, samples) in measurement {
+        let retried: Vec<&Sample> = samples.iter().filter(|sample| sample.retries > 0).collect();
+        if retried.is_empty() {
+            continue;
+        }
+        let recovered = retried
+            .iter()
+            .filter(|sample| sample.outcome == Outcome::Success)
+            .count();
+        let retries: u32 = retried.iter().map(|sample| sample.retries).sum();
+        let retry_time: f64 = retried.iter().map(|sample| sample.retry_time).sum();
+        rows.push((
+            action.name(),
+            retried.len(),
+            recovered,
+            retries,
+            retry_time / retried.len() as f64,
+        ));
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    println!(
+        "{0: <15} | {1: <8} | {2: <9} | {3: <8} | {4: <15}",
+        "Action", "Retried", "Recovered", "Retries", "Mean retry time"
+    );
+    for (action, retried, recovered, retries, mean_retry_time) in rows {
+        println!(
+            "{0: <15} | {1: <8} | {2: <9} | {3: <8} | {4: <15.4}",
+            action, retried, recovered, retries, mean_retry_time
+        );
+    }
+}
+
+/// Prints the propagation delay of the changes that became visible and the number of changes that
+/// never did. Prints nothing if the consistency was not checked.
+pub fn print_consistency(measurement: &Measurement) {
+    let mut rows = vec![];
+    for (action, samples) in measurement {
+        let checks: Vec<&Consistency> = samples
+            .iter()
+            .filter_map(|sample| sample.consistency.as_ref())
+            .collect();
+        if checks.is_empty() {
+            continue;
+        }
+        let delays: Vec<f64> = checks
+            .iter()
+            .filter(|check| !check.inconsistent)
+            .map(|check| check.delay)
+            .collect();
+        let stats = calculate_stats(&delays);
+        rows.push((
+            action.name(),
+            checks.len(),
+            checks.len() - delays.len(),
+            stats,
+        ));
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    println!(
+        "{0: <15} | {1: <8} | {2: <12} | {3: <10} | {4: <10} | {5: <10} | {6: <10}",
+        "Action", "Checked", "Inconsistent", "Mean delay", "p50 delay", "p95 delay", "Max delay"
+    );
+    for (action, checked, inconsistent, stats) in rows {
+        println!(
+            "{0: <15} | {1: <8} | {2: <12} | {3: <10.4} | {4: <10.4} | {5: <10.4} | {6: <10.4}",
+            action, checked, inconsistent, stats.mean, stats.p50, stats.p95, stats.max
+        );
+    }
+}
+
+/// Prints the size of the packed DID documents and the storage deposit of the Alias Outputs
+/// the actions published. Prints nothing if no action published a document.
+pub fn print_output_sizes(measurement: &Measurement) {
+    let mut rows = vec![];
+    for (action, samples) in measurement {
+        let sizes: Vec<&OutputSize> = samples
+            .iter()
+            .filter_map(|sample| sample.output_size.as_ref())
+            .collect();
+        if sizes.is_empty() {
+            continue;
+        }
+        let document_sizes: Vec<f64> = sizes.iter().map(|size| size.document_size as f64).collect();
+        let deposits: Vec<f64> = sizes
+            .iter()
+            .map(|size| size.storage_deposit as f64)
+            .collect();
+        rows.push((
+            action.name(),
+            sizes.len(),
+            calculate_stats(&document_sizes),
+            calculate_stats(&deposits),
+        ));
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    println!(
+        "{0: <15} | {1: <8} | {2: <12} | {3: <12} | {4: <12} | {5: <14} | {6: <14}",
+        "Action",
+        "Outputs",
+        "Min size (B)",
+        "Mean size (B)",
+        "Max size (B)",
+        "Mean deposit",
+        "Max deposit"
+    );
+    for (action, outputs, sizes, deposits) in rows {
+        println!(
+            "{0: <15} | {1: <8} | {2: <12} | {3: <12.1} | {4: <12} | {5: <14.0} | {6: <14}",
+            action, outputs, sizes.min, sizes.mean, sizes.max, deposits.mean, deposits.max
+        );
+    }
+}
+
+/// Prints the mean duration and document size of the actions per number of services in the
+/// published document. Prints nothing if no published document had a service.
+pub fn print_latency_by_services(measurement: &Measurement) {
+    let mut rows = vec![];
+    for (action, samples) in measurement {
+        let mut by_services: BTreeMap<usize, Vec<&Sample>> = BTreeMap::new();
+        for sample in samples {
+            if let (Outcome::Success, Some(size)) = (sample.outcome, &sample.output_size) {
+                by_services.entry(size.services).or_default().push(sample);
+            }
+        }
+        if by_services.keys().all(|services| *services == 0) {
+            continue;
+        }
+        for (services, samples) in by_services {
+            let durations: Vec<f64> = samples.iter().map(|sample| sample.duration).collect();
+            let sizes: Vec<f64> = samples
+                .iter()
+                .filter_map(|sample| sample.output_size)
+                .map(|size| size.document_size as f64)
+                .collect();
+            rows.push((
                 action.name(),
-                durations.clone().min(),
-                durations.clone().max(),
-                durations.clone().mean(),
-                durations.clone().variance(),
-            );
+                services,
+                samples.len(),
+                mean_of(&durations),
+                mean_of(&sizes),
+            ));
+        }
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    println!(
+        "{0: <15} | {1: <8} | {2: <8} | {3: <10} | {4: <13}",
+        "Action", "Services", "Samples", "Mean", "Mean size (B)"
+    );
+    for (action, services, samples, mean, size) in rows {
+        println!(
+            "{0: <15} | {1: <8} | {2: <8} | {3: <10.4} | {4: <13.1}",
+            action, services, samples, mean, size
+        );
+    }
+}
+
+/// Prints the tokens the successful actions locked in or reclaimed from the Alias Outputs, and the
+/// net deposit per DID over all its actions. Prints nothing if no action changed an Alias Output.
+pub fn print_token_costs(measurement: &Measurement) {
+    let mut rows = vec![];
+    // Net deposit per DID, keyed by task and index
+    let mut lifecycles: HashMap<(Option<usize>, Option<usize>), i64> = HashMap::new();
+    for action in Action::iter() {
+        let Some(samples) = measurement.get(&action) else {
+            continue;
+        };
+        let costs: Vec<(&Sample, TokenCost)> = samples
+            .iter()
+            .filter(|sample| sample.outcome == Outcome::Success)
+            .filter_map(|sample| Some((sample, sample.token_cost?)))
+            .collect();
+        if costs.is_empty() {
+            continue;
+        }
+        for (sample, cost) in &costs {
+            *lifecycles
+                .entry((sample.task, sample.iteration))
+                .or_default() += cost.deposit_change();
+        }
+        let changes: Vec<i64> = costs
+            .iter()
+            .map(|(_, cost)| cost.deposit_change())
+            .collect();
+        let balance_changes: Vec<f64> = costs
+            .iter()
+            .filter_map(|(_, cost)| cost.balance_change())
+            .map(|change| change as f64)
+            .collect();
+        rows.push((
+            action.name(),
+            costs.len(),
+            changes.iter().filter(|change| **change > 0).sum::<i64>(),
+            -changes.iter().filter(|change| **change < 0).sum::<i64>(),
+            changes.iter().sum::<i64>() as f64 / changes.len() as f64,
+            (!balance_changes.is_empty()).then(|| mean_of(&balance_changes)),
+        ));
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    println!(
+        "{0: <15} | {1: <8} | {2: <14} | {3: <14} | {4: <14} | {5: <14}",
+        "Action", "Changes", "Locked", "Reclaimed", "Mean deposit", "Mean balance"
+    );
+    for (action, count, locked, reclaimed, mean_change, mean_balance_change) in rows {
+        let mean_balance_change = match mean_balance_change {
+            Some(change) => format!("{:.0}", change),
+            None => "-".to_string(),
         };
+        println!(
+            "{0: <15} | {1: <8} | {2: <14} | {3: <14} | {4: <14.0} | {5: <14}",
+            action, count, locked, reclaimed, mean_change, mean_balance_change
+        );
+    }
+
+    let net: Vec<f64> = lifecycles.values().map(|net| *net as f64).collect();
+    println!(
+        "Net deposit per DID over {} DIDs: mean {:.0}, max {:.0}, total {:.0}",
+        net.len(),
+        mean_of(&net),
+        net.iter().cloned().fold(f64::MIN, f64::max),
+        net.iter().sum::<f64>()
+    );
+}
+
+/// Prints the end-to-end latency from the issuer to the verifier: issuing a credential, presenting
+/// it and verifying the presentation, summed per DID. Prints nothing without all three actions.
+pub fn print_credential_flow(measurement: &Measurement) {
+    let flow = [
+        Action::IssueCredential,
+        Action::CreatePresentation,
+        Action::VerifyPresentation,
+    ];
+    // Duration per DID, keyed by task and index, and the number of successful steps
+    let mut totals: HashMap<(Option<usize>, Option<usize>), (f64, usize)> = HashMap::new();
+    for action in flow {
+        let Some(samples) = measurement.get(&action) else {
+            return;
+        };
+        for sample in samples {
+            if sample.outcome == Outcome::Success {
+                let total = totals.entry((sample.task, sample.iteration)).or_default();
+                total.0 += sample.duration;
+                total.1 += 1;
+            }
+        }
+    }
+
+    let durations: Vec<f64> = totals
+        .values()
+        .filter(|(_, steps)| *steps == flow.len())
+        .map(|(duration, _)| *duration)
+        .collect();
+    if durations.is_empty() {
+        return;
+    }
+    let stats = calculate_stats(&durations);
+    println!(
+        "Issuer to verifier over {} DIDs: mean {:.4}, p50 {:.4}, p95 {:.4}, max {:.4}",
+        stats.count, stats.mean, stats.p50, stats.p95, stats.max
+    );
+}
+
+/// Prints the document size and storage deposit after revoking or unrevoking credentials, grouped
+/// by the number of revoked indices in the bitmap (0, 1-9, 10-99, ...).
+/// Prints nothing if no credential was revoked.
+pub fn print_revocation_sizes(measurement: &Measurement) {
+    let mut rows = vec![];
+    for action in [Action::RevokeCredential, Action::UnrevokeCredential] {
+        let Some(samples) = measurement.get(&action) else {
+            continue;
+        };
+        let mut by_revoked: BTreeMap<u64, Vec<OutputSize>> = BTreeMap::new();
+        for sample in samples
+            .iter()
+            .filter(|sample| sample.outcome == Outcome::Success)
+        {
+            if let Some(size) = sample.output_size {
+                by_revoked
+                    .entry(revoked_bucket(size.revoked))
+                    .or_default()
+                    .push(size);
+            }
+        }
+        for (bucket, sizes) in by_revoked {
+            let document_sizes: Vec<f64> =
+                sizes.iter().map(|size| size.document_size as f64).collect();
+            let deposits: Vec<f64> = sizes
+                .iter()
+                .map(|size| size.storage_deposit as f64)
+                .collect();
+            rows.push((
+                action.name(),
+                bucket,
+                sizes.len(),
+                mean_of(&document_sizes),
+                mean_of(&deposits),
+            ));
+        }
+    }
+    if rows.is_empty() {
+        return;
+    }
+
+    println!(
+        "{0: <20} | {1: <10} | {2: <8} | {3: <13} | {4: <14}",
+        "Action", "Revoked >=", "Samples", "Mean size (B)", "Mean deposit"
+    );
+    for (action, bucket, samples, size, deposit) in rows {
+        println!(
+            "{0: <20} | {1: <10} | {2: <8} | {3: <13.1} | {4: <14.0}",
+            action, bucket, samples, size, deposit
+        );
+    }
+}
+
+/// Largest power of ten not above `revoked`, or 0.
+fn revoked_bucket(revoked: u64) -> u64 {
+    match revoked {
+        0 => 0,
+        _ => 10u64.pow(revoked.ilog10()),
     }
 }
 
@@ -310,6 +1135,16 @@ pub fn save_to_raw_data_file(data: String, folder_path: &str) -> anyhow::Result<
     Ok(())
 }
 
+pub fn save_to_file(data: String, folder_path: &str, file_name: &str) -> anyhow::Result<()> {
+    let file_name = format!("{}/{}", folder_path, file_name);
+
+    // Create a file and write the JSON string to it
+    let mut file = File::create(file_name)?;
+    file.write_all(data.as_bytes())?;
+
+    Ok(())
+}
+
 pub fn load_from_file(folder_path: &str) -> anyhow::Result<String> {
     let file_name = format!("{}/raw_data", folder_path);
 
@@ -317,3 +1152,70 @@ pub fn load_from_file(folder_path: &str) -> anyhow::Result<String> {
     // let my_struct: MyStruct = from_str(&json_data).unwrap();
     Ok(json_data)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_raw_data_with_plain_durations() {
+        let json_data = r#"{"Localhost": {"CreateDid": [4.25, 5.5], "DeleteDid": [4.0]}}"#;
+        let raw_data = parse_raw_data(json_data).unwrap();
+
+        let samples = &raw_data["Localhost"][&Action::CreateDid];
+        assert_eq!(durations(samples), vec![4.25, 5.5]);
+        assert!(samples.iter().all(|sample| sample.start.is_none()
+            && sample.task.is_none()
+            && sample.iteration.is_none()
+            && sample.outcome == Outcome::Success));
+        assert_eq!(raw_data["Localhost"][&Action::DeleteDid].len(), 1);
+    }
+
+    #[test]
+    fn parse_block_test_raw_data_with_plain_durations() {
+        let json_data = r#"{"CreateAndPostBlock": [0.5, 0.75], "nodes_2": [0.25]}"#;
+        let raw_data = parse_raw_data(json_data).unwrap();
+
+        assert_eq!(raw_data.len(), 1);
+        let measurement = &raw_data[BLOCK_TEST_KEY];
+        assert_eq!(
+            durations(&measurement[&Action::CreateAndPostBlock]),
+            vec![0.5, 0.75]
+        );
+        assert_eq!(durations(&measurement[&Action::nodes_2]), vec![0.25]);
+    }
+
+    #[test]
+    fn parse_raw_data_with_samples() {
+        let start = Utc::now();
+        let failed = Sample::new(start, Duration::from_millis(1500), 1, 2)
+            .with_node("http://localhost:14265")
+            .with_retries(1, Duration::from_millis(500))
+            .failed(FailureKind::NodeServerError);
+        let measurement = HashMap::from([(
+            Action::UpdateDid,
+            vec![Sample::new(start, Duration::from_secs(1), 0, 0), failed],
+        )]);
+        let json_data =
+            serde_json::to_string(&HashMap::from([("Localhost", measurement)])).unwrap();
+
+        let samples = &parse_raw_data(&json_data).unwrap()["Localhost"][&Action::UpdateDid];
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].start, Some(start));
+        assert_eq!(samples[0].outcome, Outcome::Success);
+        assert_eq!(samples[1].duration, 1.5);
+        assert_eq!(samples[1].task, Some(1));
+        assert_eq!(samples[1].iteration, Some(2));
+        assert_eq!(samples[1].node.as_deref(), Some("http://localhost:14265"));
+        assert_eq!(samples[1].retries, 1);
+        assert_eq!(samples[1].retry_time, 0.5);
+        assert_eq!(samples[1].error_kind, Some(FailureKind::NodeServerError));
+        // Only successful samples are timed
+        assert_eq!(durations(samples), vec![1.0]);
+    }
+
+    #[test]
+    fn parse_raw_data_rejects_other_content() {
+        assert!(parse_raw_data(r#"{"Localhost": {"CreateDid": ["fast"]}}"#).is_err());
+    }
+}